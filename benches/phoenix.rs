@@ -21,7 +21,7 @@ fn verify_random(proved_txs: &[Transaction]) {
 }
 
 fn benchmark_phoenix(c: &mut Criterion) {
-    zk::init();
+    zk::init().unwrap();
 
     let rng = rand::thread_rng();
     let txs: Vec<Transaction> = rng.sample_iter(Standard).take(5).collect();
@@ -57,14 +57,22 @@ pub trait MerkleProofProvider {
             levels[l as usize].idx -= idx * ARITY;
         }
 
-        // TODO - Kelvin should provide the correct proof
-        Ok(MerkleProof::mock(note.hash()))
+        Ok(MerkleProof::new(levels))
     }
 
     /// Return the merkle root of the state
     fn root(&self) -> Result<BlsScalar, Error>;
 }
 
+/// Perform the poseidon permutation over a set of leaves of a tree level, producing the node of
+/// the parent level
+pub fn hash_leaves(leaves: [Option<BlsScalar>; ARITY]) -> BlsScalar {
+    let mut perm = [BlsScalar::zero(); hades252::WIDTH];
+
+    leaves_to_perm(leaves, &mut perm);
+    ScalarStrategy::new().poseidon(&mut perm)
+}
+
 fn leaves_to_perm(leaves: [Option<BlsScalar>; ARITY], perm: &mut [BlsScalar; hades252::WIDTH]) {
     let bitflags = leaves.iter().enumerate().zip(perm.iter_mut().skip(1)).fold(
         0u8,
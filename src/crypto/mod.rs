@@ -1,9 +1,10 @@
 use crate::{
-    utils, BlsScalar, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, PublicKey, ViewKey,
+    utils, BlsScalar, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, PublicKey, ViewKey,
 };
 
-use std::ptr;
+use std::{iter, ptr};
 
+use blake2::{Blake2b, Digest};
 use rand::seq::SliceRandom;
 
 use sodiumoxide::crypto::secretbox::{self, Key};
@@ -16,6 +17,9 @@ pub use poseidon252::sponge::sponge::sponge_hash;
 #[cfg(test)]
 mod tests;
 
+/// Domain separation of the view tag hash
+const VIEW_TAG_DOMAIN: &[u8] = b"phoenix-view-tag";
+
 lazy_static::lazy_static! {
     static ref HASH_BITFLAGS: [BlsScalar; hades252::WIDTH] = {
         let mut bitflags = [BlsScalar::zero(); hades252::WIDTH];
@@ -58,14 +62,91 @@ pub fn encrypt<V: AsRef<[u8]>>(
 }
 
 /// Decrypt a message using `r_g` as public of the sender, and `vk` as secret for the receiver
-pub fn decrypt(R: &JubJubExtended, vk: &ViewKey, nonce: &Nonce, value: &[u8]) -> Vec<u8> {
-    secretbox::open(value, nonce, &dhke(vk.a(), R)).unwrap_or({
+///
+/// Fail with [`Error::DecryptionFailed`] if the message was not encrypted for the provided view
+/// key, or if it was tampered
+pub fn decrypt(
+    R: &JubJubExtended,
+    vk: &ViewKey,
+    nonce: &Nonce,
+    value: &[u8],
+) -> Result<Vec<u8>, Error> {
+    secretbox::open(value, nonce, &dhke(vk.a(), R)).map_err(|_| Error::DecryptionFailed)
+}
+
+/// Decrypt a message using `r_g` as public of the sender, and `vk` as secret for the receiver
+///
+/// This never fails. If the authentication fails, a shuffled copy of the encrypted message is
+/// returned instead, so the returned bytes alone don't reveal the ownership of the message.
+///
+/// This is not constant-time: it branches on the authentication result, and shuffling takes a
+/// different path than decrypting. It must not be used where the timing is observable.
+pub fn decrypt_or_shuffle(
+    R: &JubJubExtended,
+    vk: &ViewKey,
+    nonce: &Nonce,
+    value: &[u8],
+) -> Vec<u8> {
+    decrypt(R, vk, nonce, value).unwrap_or_else(|_| {
         let mut value = value.to_vec();
         value.shuffle(&mut rand::thread_rng());
         value
     })
 }
 
+/// Derive the short view tag of a note from the DHKE shared secret `r · A == a · R`
+///
+/// The tag is independent from the encryption key, and allows the receiver to discard most of the
+/// notes it doesn't own without the full ownership check
+pub fn view_tag(shared_secret: &JubJubExtended) -> u8 {
+    let mut hasher = Blake2b::new();
+    hasher.update(VIEW_TAG_DOMAIN);
+    hasher.update(&JubJubAffine::from(shared_secret).to_bytes()[..]);
+
+    hasher.finalize()[0]
+}
+
+/// Number of bytes packed in every scalar by [`bytes_to_scalars`]. Any 31 bytes are below the
+/// scalar modulus, so every chunk has a canonical scalar
+pub const BYTES_PER_SCALAR: usize = utils::BLS_SCALAR_SERIALIZED_SIZE - 1;
+
+/// Map a byte array of any length to its length, followed by its little-endian chunks of
+/// [`BYTES_PER_SCALAR`] bytes. The last chunk is zero-padded.
+///
+/// The length prefix makes the mapping injective, also for concatenated sequences of arrays
+pub fn bytes_to_scalars(bytes: &[u8]) -> Vec<BlsScalar> {
+    let chunks = bytes.chunks(BYTES_PER_SCALAR).map(|chunk| {
+        let mut s = [0x00u8; utils::BLS_SCALAR_SERIALIZED_SIZE];
+        s[..chunk.len()].copy_from_slice(chunk);
+
+        // Infallible, since the most significant byte is zero
+        BlsScalar::from_bytes(&s).unwrap()
+    });
+
+    iter::once(BlsScalar::from(bytes.len() as u64))
+        .chain(chunks)
+        .collect()
+}
+
+/// Perform a poseidon sponge hash of a set of byte arrays, each mapped to scalars via
+/// [`bytes_to_scalars`], in order
+pub fn hash_bytes<B: AsRef<[u8]>>(inputs: &[B]) -> Result<BlsScalar, Error> {
+    if inputs.is_empty() {
+        return Err(Error::InvalidLength {
+            field: "hash inputs",
+            expected: 1,
+            actual: 0,
+        });
+    }
+
+    let scalars: Vec<BlsScalar> = inputs
+        .iter()
+        .flat_map(|b| bytes_to_scalars(b.as_ref()))
+        .collect();
+
+    Ok(sponge_hash(scalars.as_slice()))
+}
+
 /// Convert to a deterministic representation of the projective point, and perform `H(x, y, z, t)`
 pub fn hash_jubjub_projective(p: &JubJubExtended) -> BlsScalar {
     let p = JubJubExtended::from(JubJubAffine::from(p));
@@ -1,4 +1,9 @@
-use crate::{crypto, utils, Note, NoteGenerator, ObfuscatedNote, SecretKey};
+use crate::{
+    crypto, utils, BlsScalar, Error, JubJubScalar, Nonce, Note, NoteGenerator, Nullifier,
+    ObfuscatedNote, SecretKey, Transaction, TransactionInput, TransparentNote,
+};
+
+use std::fs;
 
 #[test]
 fn decrypt() {
@@ -11,7 +16,7 @@ fn decrypt() {
 
     let bytes = b"some data";
     let encrypted = crypto::encrypt(&r, &pk, &nonce, bytes);
-    let decrypted = crypto::decrypt(&R, &vk, &nonce, encrypted.as_slice());
+    let decrypted = crypto::decrypt(&R, &vk, &nonce, encrypted.as_slice()).unwrap();
 
     assert_eq!(&bytes[..], decrypted.as_slice());
 }
@@ -30,9 +35,14 @@ fn decrypt_with_wrong_key_should_fail() {
 
     let bytes = b"some data";
     let encrypted = crypto::encrypt(&r, &pk, &nonce, bytes);
-    let decrypted = crypto::decrypt(&r_g, &wrong_vk, &nonce, encrypted.as_slice());
+    match crypto::decrypt(&r_g, &wrong_vk, &nonce, encrypted.as_slice()) {
+        Err(Error::DecryptionFailed) => (),
+        r => panic!("Unexpected decrypt result {:?}", r),
+    }
 
+    let decrypted = crypto::decrypt_or_shuffle(&r_g, &wrong_vk, &nonce, encrypted.as_slice());
     assert_ne!(&bytes[..], decrypted.as_slice());
+    assert_eq!(encrypted.len(), decrypted.len());
 }
 
 #[test]
@@ -47,4 +57,189 @@ fn decrypt_obfuscated_note() {
     let decrypt_value = note.value(Some(&vk));
 
     assert_eq!(decrypt_value, value);
+    assert_eq!(value, note.try_value(&vk).unwrap());
+}
+
+#[test]
+fn decrypt_obfuscated_note_with_wrong_key_should_fail() {
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+    let wrong_vk = SecretKey::default().view_key();
+
+    let (note, blinding_factor) = ObfuscatedNote::output(&pk, 25);
+
+    match note.try_value(&wrong_vk) {
+        Err(Error::DecryptionFailed) => (),
+        r => panic!("Unexpected value result {:?}", r),
+    }
+
+    match note.try_blinding_factor(&wrong_vk) {
+        Err(Error::DecryptionFailed) => (),
+        r => panic!("Unexpected blinding factor result {:?}", r),
+    }
+
+    assert_eq!(
+        blinding_factor,
+        note.try_blinding_factor(&sk.view_key()).unwrap()
+    );
+
+    let (note, _) = TransparentNote::output(&pk, 25);
+    assert_eq!(25, note.try_value(&wrong_vk).unwrap());
+}
+
+/// Parse the space-separated hex byte arrays of a vector, where `_` is the empty array
+fn vector_inputs(inputs: &str) -> Vec<Vec<u8>> {
+    inputs
+        .split(' ')
+        .map(|i| match i {
+            "_" => vec![],
+            i => hex::decode(i).unwrap(),
+        })
+        .collect()
+}
+
+/// Deterministic transactions of the transaction hash vectors
+fn vector_transactions() -> Vec<Transaction> {
+    let pk = SecretKey::from(&b"hash vectors"[..]).public_key();
+    let output = |seed: u64, value: u64| {
+        let blinding_factor = JubJubScalar::from(seed + 1000);
+        let note = TransparentNote::deterministic_output(
+            &JubJubScalar::from(seed),
+            Nonce([seed as u8; 24]),
+            &pk,
+            value,
+            blinding_factor,
+        );
+
+        note.to_transaction_output(value, blinding_factor, pk)
+    };
+
+    (1..4u64)
+        .map(|n| {
+            let mut tx = Transaction::default();
+
+            (0..n).for_each(|i| {
+                let nullifier = Nullifier::from(BlsScalar::from(10 * n + i));
+                let input = TransactionInput::obfuscated(nullifier, BlsScalar::from(n));
+                tx.push_input(input).unwrap();
+            });
+
+            (0..n.min(2)).for_each(|i| {
+                tx.push_output(output(10 * n + i, 25 * (i + 1))).unwrap();
+            });
+
+            tx.set_fee(output(100 + n, n));
+            tx
+        })
+        .collect()
+}
+
+#[test]
+fn hash_bytes_vectors() {
+    let vectors = include_str!("hash_vectors.txt")
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("tx "));
+
+    for vector in vectors {
+        let mut vector = vector.split(" -> ");
+        let inputs = vector_inputs(vector.next().unwrap());
+        let expected = vector.next().unwrap();
+
+        let scalars: Vec<BlsScalar> = expected
+            .split(' ')
+            .map(|s| utils::deserialize_bls_scalar(hex::decode(s).unwrap().as_slice()).unwrap())
+            .collect();
+        let mapped: Vec<BlsScalar> = inputs
+            .iter()
+            .flat_map(|input| crypto::bytes_to_scalars(input))
+            .collect();
+        assert_eq!(scalars, mapped);
+
+        let hash = crypto::hash_bytes(inputs.as_slice()).unwrap();
+        assert_eq!(crypto::sponge_hash(scalars.as_slice()), hash);
+
+        if let Some(digest) = vector.next() {
+            assert_eq!(digest, hex::encode(hash.to_bytes()));
+        }
+    }
+
+    // The arrays are length-prefixed, so splitting or padding them changes the hash
+    let hash = |inputs: &[&[u8]]| crypto::hash_bytes(inputs).unwrap();
+    assert_ne!(hash(&[&[0x01], &[0x02]]), hash(&[&[0x01, 0x02]]));
+    assert_ne!(hash(&[&[0x01]]), hash(&[&[0x01, 0x00]]));
+    assert_ne!(hash(&[&[], &[0x00]]), hash(&[&[0x00]]));
+
+    match crypto::hash_bytes::<&[u8]>(&[]) {
+        Err(Error::InvalidLength { actual: 0, .. }) => (),
+        r => panic!("Unexpected hash result {:?}", r),
+    }
+}
+
+#[test]
+fn transaction_hash_vectors() {
+    let vectors = include_str!("hash_vectors.txt")
+        .lines()
+        .filter(|l| l.starts_with("tx "))
+        .map(|l| &l["tx ".len()..]);
+
+    for (vector, generated) in vectors.zip(vector_transactions()) {
+        let mut vector = vector.split(" -> ");
+        let bytes = hex::decode(vector.next().unwrap()).unwrap();
+        let digest = vector.next().unwrap();
+
+        let tx = Transaction::from_bytes(bytes.as_slice()).unwrap();
+        assert_eq!(bytes, tx.to_bytes().unwrap());
+        assert_eq!(bytes, generated.to_bytes().unwrap());
+        assert_eq!(digest, hex::encode(tx.hash().to_bytes()));
+    }
+}
+
+/// Write the digests of the binary inputs and the transaction vectors of `hash_vectors.txt`,
+/// computed by this implementation
+#[test]
+#[ignore]
+fn generate_hash_vectors() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/crypto/hash_vectors.txt");
+    let vectors = fs::read_to_string(path).unwrap();
+
+    let mut lines: Vec<String> = vectors
+        .lines()
+        .filter(|l| l.starts_with('#'))
+        .map(String::from)
+        .collect();
+
+    vectors
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("tx "))
+        .for_each(|l| {
+            let inputs = l.split(" -> ").next().unwrap();
+            let bytes = vector_inputs(inputs);
+
+            let scalars: Vec<String> = bytes
+                .iter()
+                .flat_map(|b| crypto::bytes_to_scalars(b))
+                .map(|s| hex::encode(s.to_bytes()))
+                .collect();
+            let digest = crypto::hash_bytes(bytes.as_slice()).unwrap();
+
+            lines.push(format!(
+                "{} -> {} -> {}",
+                inputs,
+                scalars.join(" "),
+                hex::encode(digest.to_bytes())
+            ));
+        });
+
+    vector_transactions().iter().for_each(|tx| {
+        let bytes = tx.to_bytes().unwrap();
+        let digest = Transaction::from_bytes(bytes.as_slice()).unwrap().hash();
+
+        lines.push(format!(
+            "tx {} -> {}",
+            hex::encode(bytes),
+            hex::encode(digest.to_bytes())
+        ));
+    });
+
+    fs::write(path, lines.join("\n") + "\n").unwrap();
 }
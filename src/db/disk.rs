@@ -0,0 +1,150 @@
+use crate::Error;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bytehash::ByteHash;
+use kelvin::{Content, Store};
+
+/// Name of the file, inside the db directory, that holds the last committed root
+pub const ROOT_FILE: &str = "ROOT";
+
+/// Name of the directory, inside the db directory, of the kelvin store
+pub const STORE_DIR: &str = "store";
+
+/// Kelvin on-disk store of a [`super::Db`], and the pointer to its last committed root.
+///
+/// The store is content-addressed and only grows, so a commit is only visible after the root file
+/// is atomically replaced. The store is synced to disk before the root file is replaced, so an
+/// interrupted commit leaves the previous root in place, and a committed root is never missing
+/// from the store. Only the files written by the commit are synced, so the cost of a commit
+/// doesn't grow with the history of the store.
+#[derive(Clone)]
+pub(crate) struct DiskStore<H: ByteHash> {
+    store: Store<H>,
+    path: PathBuf,
+}
+
+impl<H: ByteHash> DiskStore<H> {
+    /// Open the store of the provided directory, creating it if required
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let store_path = path.join(STORE_DIR);
+
+        fs::create_dir_all(&store_path)?;
+        let store = Store::new(&store_path)?;
+
+        Ok(Self { store, path })
+    }
+
+    /// Last committed root, if any
+    pub fn root(&self) -> Result<Option<H::Digest>, Error> {
+        let path = self.path.join(ROOT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let bytes = hex::decode(fs::read(path)?).map_err(|_| Error::InvalidEncoding(ROOT_FILE))?;
+
+        let mut root = H::Digest::default();
+        if bytes.len() != root.as_ref().len() {
+            return Err(Error::InvalidLength {
+                field: ROOT_FILE,
+                expected: root.as_ref().len(),
+                actual: bytes.len(),
+            });
+        }
+        root.as_mut().copy_from_slice(bytes.as_slice());
+
+        Ok(Some(root))
+    }
+
+    /// Persist the content on the store, and set it as the last committed root
+    pub fn commit<C: Content<H>>(&self, content: &mut C) -> Result<H::Digest, Error> {
+        let store_path = self.path.join(STORE_DIR);
+
+        let mut before = HashMap::new();
+        list_tree(&store_path, &mut before)?;
+
+        let root = self.store.persist(content)?.hash().clone();
+        sync_changes(&store_path, &before)?;
+
+        let path = self.path.join(ROOT_FILE);
+        let tmp = path.with_extension("tmp");
+
+        let mut file = File::create(&tmp)?;
+        file.write_all(hex::encode(root.as_ref()).as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+
+        // The rename is only durable once the directory entry is synced
+        File::open(&self.path)?.sync_all()?;
+
+        Ok(root)
+    }
+
+    /// Restore the content committed with the provided root
+    pub fn restore<C: Content<H>>(&self, root: &H::Digest) -> Result<C, Error> {
+        let snapshot = self.store.get_snapshot(root)?;
+
+        self.store.restore(&snapshot).map_err(|e| e.into())
+    }
+}
+
+/// Length and modification time of a file, to find the files written by a commit
+type FileStamp = (u64, Option<SystemTime>);
+
+/// Collect the stamps of the files of the directory and its subdirectories
+fn list_tree(path: &Path, files: &mut HashMap<PathBuf, FileStamp>) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            list_tree(&entry.path(), files)?;
+        } else {
+            files.insert(entry.path(), (metadata.len(), metadata.modified().ok()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Sync to disk the files that are new or changed since the provided stamps were collected, and
+/// the directories with new entries. Return true if the tree of the directory has new entries, so
+/// the entries of new subdirectories are synced on their parent as well.
+///
+/// The store only appends, so every written file changes its length or modification time
+fn sync_changes(path: &Path, before: &HashMap<PathBuf, FileStamp>) -> io::Result<bool> {
+    let mut new_entries = false;
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let entry_path = entry.path();
+
+        if metadata.is_dir() {
+            new_entries |= sync_changes(&entry_path, before)?;
+            continue;
+        }
+
+        let stamp = (metadata.len(), metadata.modified().ok());
+        match before.get(&entry_path) {
+            Some(s) if *s == stamp => (),
+            Some(_) => File::open(&entry_path)?.sync_all()?,
+            None => {
+                File::open(&entry_path)?.sync_all()?;
+                new_entries = true;
+            }
+        }
+    }
+
+    if new_entries {
+        File::open(path)?.sync_all()?;
+    }
+
+    Ok(new_entries)
+}
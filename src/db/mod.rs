@@ -1,21 +1,49 @@
 use crate::{
-    crypto, BlsScalar, Error, MerkleProofProvider, Note, NoteVariant, Nullifier, Transaction,
-    TransactionItem, MAX_NOTES_PER_TRANSACTION,
+    crypto, BlsScalar, Error, MerkleProofProvider, Note, NoteType, NoteVariant, Nullifier,
+    Transaction, TransactionItem, MAX_INPUT_NOTES_PER_TRANSACTION,
+    MAX_OUTPUT_NOTES_PER_TRANSACTION,
 };
 
+use std::collections::VecDeque;
 use std::io;
+use std::ops::{Bound, Range, RangeBounds};
+use std::path::Path;
 
 use bytehash::ByteHash;
 use kelvin::annotations::Count;
 use kelvin::{Blake2b, Content, Sink, Source};
 use kelvin_hamt::CountingHAMTMap as HAMTMap;
 use kelvin_radix::DefaultRadixMap as RadixMap;
+use poseidon252::StorageScalar;
 use tracing::trace;
 
 /// Type used for notes storage
 pub type NotesDb = Db<Blake2b>;
 /// Type used for notes iterator
-pub type NotesIter = DbNotesIterator<Blake2b>;
+pub type NotesIter<'a> = DbNotesIterator<'a, Blake2b>;
+
+/// Maximum number of blocks that can be reverted with [`Db::revert_block`]
+pub const MAX_REVERTIBLE_BLOCKS: usize = 32;
+
+use disk::DiskStore;
+use nullifiers::NullifiersTree;
+use undo::BlockUndo;
+
+pub use disk::{ROOT_FILE, STORE_DIR};
+pub use nullifiers::{NullifierProof, NullifiersLevel, NULLIFIERS_TREE_HEIGHT};
+pub use roots::{RootWindow, DEFAULT_ROOT_WINDOW};
+pub use validation::ValidationError;
+
+/// Kelvin on-disk store of the notes db
+pub mod disk;
+/// Sparse merkle tree of the spent nullifiers
+pub mod nullifiers;
+/// Bounded window of the recent roots of the notes tree
+pub mod roots;
+/// Undo logs of the revertible blocks
+pub mod undo;
+/// Stateful transaction validation errors
+pub mod validation;
 
 #[cfg(test)]
 mod tests;
@@ -25,6 +53,11 @@ mod tests;
 pub struct Db<H: ByteHash> {
     notes: HAMTMap<u64, NoteVariant, H>,
     nullifiers: RadixMap<Nullifier, (), H>,
+    nullifiers_tree: NullifiersTree<H>,
+    tree: HAMTMap<u64, StorageScalar, H>,
+    roots: RootWindow,
+    blocks: VecDeque<BlockUndo>,
+    disk: Option<DiskStore<H>>,
 }
 
 impl<H: ByteHash> Default for Db<H> {
@@ -32,6 +65,11 @@ impl<H: ByteHash> Default for Db<H> {
         Db {
             notes: HAMTMap::default(),
             nullifiers: RadixMap::default(),
+            nullifiers_tree: NullifiersTree::default(),
+            tree: HAMTMap::default(),
+            roots: RootWindow::default(),
+            blocks: VecDeque::new(),
+            disk: None,
         }
     }
 }
@@ -39,37 +77,70 @@ impl<H: ByteHash> Default for Db<H> {
 impl<H: ByteHash> Content<H> for Db<H> {
     fn persist(&mut self, sink: &mut Sink<H>) -> io::Result<()> {
         self.notes.persist(sink)?;
-        self.nullifiers.persist(sink)
+        self.nullifiers.persist(sink)?;
+        self.nullifiers_tree.persist(sink)?;
+        self.tree.persist(sink)?;
+        self.roots.persist(sink)?;
+
+        (self.blocks.len() as u64).persist(sink)?;
+        self.blocks.iter_mut().try_for_each(|b| b.persist(sink))
     }
 
     fn restore(source: &mut Source<H>) -> io::Result<Self> {
+        let notes = HAMTMap::restore(source)?;
+        let nullifiers = RadixMap::restore(source)?;
+        let nullifiers_tree = NullifiersTree::restore(source)?;
+        let tree = HAMTMap::restore(source)?;
+        let roots = RootWindow::restore(source)?;
+
+        let blocks = u64::restore(source)?;
+        let blocks = (0..blocks)
+            .map(|_| BlockUndo::restore(source))
+            .collect::<io::Result<VecDeque<BlockUndo>>>()?;
+
         Ok(Db {
-            notes: HAMTMap::restore(source)?,
-            nullifiers: RadixMap::restore(source)?,
+            notes,
+            nullifiers,
+            nullifiers_tree,
+            tree,
+            roots,
+            blocks,
+            disk: None,
         })
     }
 }
 
+/// Key of a tree node on the storage, provided its depth and position on the level.
+///
+/// The leaves level holds at most `ARITY ^ (TREE_HEIGHT - 1)` nodes, so the position always fits
+/// the lower 32 bits.
+fn tree_key(depth: u32, idx: usize) -> u64 {
+    ((depth as u64) << 32) | idx as u64
+}
+
 impl<H: ByteHash> crypto::MerkleProofProvider for Db<H> {
     fn query_level(
         &self,
-        _depth: u32,
-        _idx: usize,
+        depth: u32,
+        idx: usize,
     ) -> Result<[Option<BlsScalar>; crypto::ARITY], Error> {
-        // TODO - Implement
-        let mut rng = rand::thread_rng();
+        let first = idx - idx % crypto::ARITY;
 
         let mut leaves = [None; crypto::ARITY];
         leaves
             .iter_mut()
-            .for_each(|l| *l = Some(BlsScalar::random(&mut rng)));
+            .enumerate()
+            .try_for_each::<_, Result<_, Error>>(|(i, l)| {
+                *l = self.tree_node(depth, first + i)?;
+                Ok(())
+            })?;
 
         Ok(leaves)
     }
 
     fn root(&self) -> Result<BlsScalar, Error> {
-        // TODO - Implement
-        Ok(BlsScalar::random(&mut rand::thread_rng()))
+        self.tree_node(crypto::TREE_HEIGHT as u32 - 1, 0)
+            .map(|r| r.unwrap_or_else(BlsScalar::zero))
     }
 }
 
@@ -81,11 +152,8 @@ pub fn merkle_opening(
     state.opening(note)
 }
 
-/// Store a provided [`Transaction`]. Return the position of the note on the tree.
-pub fn store(
-    state: &mut Db<Blake2b>,
-    transaction: &Transaction,
-) -> Result<[Option<u64>; MAX_NOTES_PER_TRANSACTION], Error> {
+/// Store a provided [`Transaction`]. Return the positions of the fee and output notes on the tree.
+pub fn store(state: &mut Db<Blake2b>, transaction: &Transaction) -> Result<Vec<u64>, Error> {
     let v = state.store_transaction(transaction)?;
 
     Ok(v)
@@ -96,74 +164,308 @@ impl<H: ByteHash> Db<H> {
         Default::default()
     }
 
-    pub fn store_transaction(
+    /// Create a new db that accepts the transaction inputs proven against any of the last
+    /// `capacity` roots of the notes tree
+    pub fn with_root_window(capacity: usize) -> Db<H> {
+        Db {
+            roots: RootWindow::new(capacity),
+            ..Default::default()
+        }
+    }
+
+    /// Open the db stored on the provided directory, at its last committed root.
+    ///
+    /// If the directory contains no commits, an empty db backed by it is created.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Db<H>, Error> {
+        let disk = DiskStore::open(path)?;
+
+        match disk.root()? {
+            Some(root) => Self::restore_from(disk, &root),
+            None => Ok(Db {
+                disk: Some(disk),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Open the db stored on the provided directory, at the provided committed root
+    pub fn restore<P: AsRef<Path>>(path: P, root: &H::Digest) -> Result<Db<H>, Error> {
+        Self::restore_from(DiskStore::open(path)?, root)
+    }
+
+    /// Persist the current state on the directory the db was opened from, and return its root.
+    ///
+    /// The state of the last commit is the one reloaded by [`Db::open`]. Fail with
+    /// [`Error::NotReady`] if the db is not backed by a directory.
+    pub fn commit(&mut self) -> Result<H::Digest, Error> {
+        let disk = self.disk.take().ok_or(Error::NotReady)?;
+        let root = disk.commit(self);
+        self.disk.replace(disk);
+
+        root
+    }
+
+    fn restore_from(disk: DiskStore<H>, root: &H::Digest) -> Result<Db<H>, Error> {
+        let mut db: Db<H> = disk.restore(root)?;
+        db.disk.replace(disk);
+
+        Ok(db)
+    }
+
+    /// Recent roots of the notes tree accepted for the transaction inputs
+    pub fn root_window(&self) -> &RootWindow {
+        &self.roots
+    }
+
+    /// Change the number of recent roots of the notes tree accepted for the transaction inputs
+    pub fn set_root_window(&mut self, capacity: usize) {
+        self.roots.set_capacity(capacity);
+    }
+
+    /// Store a [`Transaction`]. Return the positions of the fee and output notes on the tree.
+    ///
+    /// The nullifiers are checked before any insertion, so a double spending transaction leaves
+    /// the state untouched.
+    pub fn store_transaction(&mut self, transaction: &Transaction) -> Result<Vec<u64>, Error> {
+        self.insert_transaction(transaction, None)
+    }
+
+    /// Store a [`Transaction`], and append its nullifiers to the undo log of the block being
+    /// applied, if any
+    fn insert_transaction(
         &mut self,
         transaction: &Transaction,
-    ) -> Result<[Option<u64>; MAX_NOTES_PER_TRANSACTION], Error> {
-        transaction
-            .inputs()
-            .iter()
-            .try_fold::<_, _, Result<_, Error>>(None, |_, i| {
-                let n = *i.nullifier();
+        undo: Option<&mut BlockUndo>,
+    ) -> Result<Vec<u64>, Error> {
+        let inputs = transaction.inputs();
 
-                self.fetch_nullifier(&n)?
-                    .map(|_| Err(Error::DoubleSpending))
-                    .unwrap_or(Ok(()))?;
+        for (i, input) in inputs.iter().enumerate() {
+            let n = input.nullifier();
 
-                self.nullifiers.insert(n, ()).map_err(|e| e.into())
-            })?;
+            if inputs[..i].iter().any(|p| p.nullifier() == n) || self.fetch_nullifier(n)?.is_some()
+            {
+                return Err(Error::DoubleSpending);
+            }
+        }
 
-        let mut idx = [None; MAX_NOTES_PER_TRANSACTION];
+        if let Some(block) = undo {
+            block
+                .nullifiers
+                .extend(inputs.iter().map(|input| *input.nullifier()));
+        }
 
-        let mut idx_iter = idx.iter_mut();
+        for input in inputs {
+            self.nullifiers.insert(*input.nullifier(), ())?;
+            self.nullifiers_tree.insert(input.nullifier())?;
+        }
+
+        let mut idx = Vec::with_capacity(1 + transaction.outputs().len());
 
         let fee = transaction.fee();
-        let fee = self.store_unspent_note(fee.note().clone())?;
-        idx_iter.next().map(|i| i.replace(fee));
+        idx.push(self.insert_note(fee.note().clone())?);
 
-        transaction
-            .outputs()
-            .iter()
-            .zip(idx_iter)
-            .map(|(o, i)| {
-                self.store_unspent_note(o.note().clone())
-                    .map(|idx| i.replace(idx))?;
+        for o in transaction.outputs() {
+            idx.push(self.insert_note(o.note().clone())?);
+        }
 
-                Ok(())
-            })
-            .collect::<Result<_, Error>>()?;
+        self.push_root()?;
 
         Ok(idx)
     }
 
-    /// Store a set of [`Transaction`]. Return a set of positions of the included notes.
+    /// Validate a [`Transaction`] against the current state, without mutating it.
+    ///
+    /// Every input must refer to a known root of the notes tree and spend an unused nullifier,
+    /// the fee must be a transparent note, and the proof must verify. The proof is checked last,
+    /// since it is the most expensive check
+    pub fn validate_transaction(&self, transaction: &Transaction) -> Result<(), ValidationError> {
+        let inputs = transaction.inputs();
+
+        if inputs.is_empty()
+            || inputs.len() > MAX_INPUT_NOTES_PER_TRANSACTION
+            || transaction.outputs().len() > MAX_OUTPUT_NOTES_PER_TRANSACTION
+        {
+            return Err(ValidationError::InvalidShape);
+        }
+
+        if transaction.fee().note().note() != NoteType::Transparent {
+            return Err(ValidationError::InvalidFee);
+        }
+
+        for (i, input) in inputs.iter().enumerate() {
+            let nullifier = input.nullifier();
+
+            if !self.is_known_root(&input.merkle_root)? {
+                return Err(ValidationError::UnknownMerkleRoot(i));
+            }
+
+            if inputs[..i].iter().any(|p| p.nullifier() == nullifier) {
+                return Err(ValidationError::DuplicatedNullifier(i));
+            }
+
+            if self.fetch_nullifier(nullifier)?.is_some() {
+                return Err(ValidationError::DoubleSpending(i));
+            }
+        }
+
+        // The verification recomputes the public inputs from the items of the clone, so a stale
+        // cache of the provided transaction is never trusted
+        transaction
+            .clone()
+            .verify()
+            .map_err(|_| ValidationError::InvalidProof)
+    }
+
+    /// Check if the provided merkle root is the current root of the notes tree, or one of the
+    /// roots of the [`RootWindow`]
+    pub fn is_known_root(&self, root: &BlsScalar) -> Result<bool, Error> {
+        Ok(self.roots.contains(root) || &self.root()? == root)
+    }
+
+    /// Store a block of [`Transaction`]. Return a set of positions of the included notes.
+    ///
+    /// The block is applied atomically: if any transaction fails, the state is left untouched.
+    /// Otherwise, an undo log of the block is kept so it can be undone with
+    /// [`Db::revert_block`].
     pub fn store_bulk_transactions(
         &mut self,
         transactions: &[Transaction],
     ) -> Result<Vec<u64>, Error> {
-        let mut idx = vec![];
+        let mut block = BlockUndo {
+            notes_count: self.notes_count(),
+            nullifiers: vec![],
+            roots: self.roots.clone(),
+        };
 
+        let mut idx = vec![];
         for t in transactions {
             trace!("Storing tx {}", t);
-            self.store_transaction(t)?
-                .iter()
-                .filter_map(|i| i.as_ref())
-                .for_each(|i| idx.push(*i));
+
+            match self.insert_transaction(t, Some(&mut block)) {
+                Ok(i) => idx.extend(i),
+                Err(e) => {
+                    self.blocks.push_back(block);
+                    self.revert_block()?;
+                    return Err(e);
+                }
+            }
+        }
+
+        self.blocks.push_back(block);
+        if self.blocks.len() > MAX_REVERTIBLE_BLOCKS {
+            self.blocks.pop_front();
         }
 
         Ok(idx)
     }
 
+    /// Restore the notes, nullifiers and merkle tree to the state previous to the last block
+    /// stored with [`Db::store_bulk_transactions`].
+    ///
+    /// Only the blocks record undo data: the notes tree is truncated to the first position of the
+    /// block, so notes stored after it are removed as well, but the nullifiers spent outside of a
+    /// block are kept. The root window keeps its current capacity.
+    ///
+    /// Up to [`MAX_REVERTIBLE_BLOCKS`] blocks can be reverted. Fail with [`Error::NotFound`] if
+    /// there is no block to revert.
+    pub fn revert_block(&mut self) -> Result<(), Error> {
+        let block = self.blocks.pop_back().ok_or(Error::NotFound)?;
+
+        for n in block.nullifiers.iter().rev() {
+            self.nullifiers.remove(n)?;
+            self.nullifiers_tree.remove(n)?;
+        }
+
+        for idx in (block.notes_count..self.notes_count()).rev() {
+            self.notes.remove(&idx)?;
+            self.remove_leaf(idx)?;
+        }
+
+        self.roots.restore_roots(block.roots);
+
+        Ok(())
+    }
+
+    /// Number of stored blocks that can be reverted
+    pub fn revertible_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
     /// Store a note. Return the position of the stored note on the tree.
-    pub fn store_unspent_note(&mut self, mut note: NoteVariant) -> Result<u64, Error> {
+    pub fn store_unspent_note(&mut self, note: NoteVariant) -> Result<u64, Error> {
+        let idx = self.insert_note(note)?;
+        self.push_root()?;
+
+        Ok(idx)
+    }
+
+    /// Insert a note on the tree without recording the new root on the window
+    fn insert_note(&mut self, mut note: NoteVariant) -> Result<u64, Error> {
         let idx = self.notes.count() as u64;
 
         note.set_idx(idx.clone());
         self.notes.insert(idx.clone(), note)?;
+        self.append_leaf(idx, note.hash())?;
 
         Ok(idx)
     }
 
+    /// Record the current root of the notes tree on the window
+    fn push_root(&mut self) -> Result<(), Error> {
+        let root = self.root()?;
+        self.roots.push(root);
+
+        Ok(())
+    }
+
+    /// Insert a leaf on the merkle tree, and recalculate the path from the leaf up to the root
+    fn append_leaf(&mut self, idx: u64, leaf: BlsScalar) -> Result<(), Error> {
+        let mut idx = idx as usize;
+        let mut node = leaf;
+
+        self.tree.insert(tree_key(0, idx), StorageScalar(node))?;
+
+        for depth in 1..crypto::TREE_HEIGHT as u32 {
+            node = crypto::merkle::hash_leaves(self.query_level(depth - 1, idx)?);
+            idx /= crypto::ARITY;
+
+            self.tree
+                .insert(tree_key(depth, idx), StorageScalar(node))?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove a leaf from the merkle tree, and recalculate the path from the leaf up to the root.
+    ///
+    /// The nodes left without children are removed, so the tree is the same as if the leaf was
+    /// never appended.
+    fn remove_leaf(&mut self, idx: u64) -> Result<(), Error> {
+        let mut idx = idx as usize;
+
+        self.tree.remove(&tree_key(0, idx))?;
+
+        for depth in 1..crypto::TREE_HEIGHT as u32 {
+            let level = self.query_level(depth - 1, idx)?;
+            idx /= crypto::ARITY;
+
+            if level.iter().all(Option::is_none) {
+                self.tree.remove(&tree_key(depth, idx))?;
+            } else {
+                let node = crypto::merkle::hash_leaves(level);
+                self.tree
+                    .insert(tree_key(depth, idx), StorageScalar(node))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return a node of the merkle tree, if present
+    fn tree_node(&self, depth: u32, idx: usize) -> Result<Option<BlsScalar>, Error> {
+        Ok(self.tree.get(&tree_key(depth, idx))?.map(|n| n.0))
+    }
+
     /// Provided a position, return a strong typed note from the database
     pub fn fetch_note(&self, idx: u64) -> Result<NoteVariant, Error> {
         self.notes
@@ -172,6 +474,11 @@ impl<H: ByteHash> Db<H> {
             .ok_or(Error::NotFound)
     }
 
+    /// Number of notes stored
+    pub fn notes_count(&self) -> u64 {
+        self.notes.count() as u64
+    }
+
     /// Verify the existence of a provided nullifier on the set
     pub fn fetch_nullifier(&self, nullifier: &Nullifier) -> Result<Option<()>, Error> {
         self.nullifiers
@@ -180,37 +487,91 @@ impl<H: ByteHash> Db<H> {
             .map_err(|e| e.into())
     }
 
+    /// Iterate the notes stored from the provided position
+    pub fn notes_from(&self, position: u64) -> DbNotesIterator<'_, H> {
+        self.notes_range(position..)
+    }
+
+    /// Iterate the notes of the provided position range. The range is clamped to the stored notes
+    pub fn notes_range<R: RangeBounds<u64>>(&self, range: R) -> DbNotesIterator<'_, H> {
+        let count = self.notes_count();
+
+        let start = match range.start_bound() {
+            Bound::Included(s) => *s,
+            Bound::Excluded(s) => s.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(e) => e.saturating_add(1),
+            Bound::Excluded(e) => *e,
+            Bound::Unbounded => count,
+        };
+
+        let end = end.min(count);
+        let start = start.min(end);
+
+        DbNotesIterator {
+            notes: &self.notes,
+            range: start..end,
+        }
+    }
+
+    /// Verify the existence of a set of nullifiers. The results follow the order of the provided
+    /// nullifiers
+    pub fn fetch_nullifiers(&self, nullifiers: &[Nullifier]) -> Result<Vec<Option<()>>, Error> {
+        nullifiers.iter().map(|n| self.fetch_nullifier(n)).collect()
+    }
+
+    /// Root of the merkle tree of the spent nullifiers, to be checked by the
+    /// [`NullifierProof`]
+    pub fn nullifiers_root(&self) -> Result<BlsScalar, Error> {
+        self.nullifiers_tree.root()
+    }
+
+    /// Create a proof that the nullifier is spent, or unspent, against
+    /// [`Db::nullifiers_root`]
+    pub fn nullifier_proof(&self, nullifier: &Nullifier) -> Result<NullifierProof, Error> {
+        self.nullifiers_tree.proof(nullifier)
+    }
+
     pub fn notes(self) -> HAMTMap<u64, NoteVariant, H> {
         self.notes
     }
 }
 
-// TODO - Very naive implementation, optimize to Kelvin
-pub struct DbNotesIterator<H: ByteHash> {
-    notes: HAMTMap<u64, NoteVariant, H>,
-    cur: u64,
+/// Iterator over the stored notes of a [`Db`], ordered by their positions on the tree.
+///
+/// The HAMT is keyed by the hash of the position, so its branches are not ordered by position.
+/// Every note is fetched with a lookup of its position on the map borrowed from the db, and
+/// cloned out of it. A note that can't be read is reported as an error instead of ending the
+/// iteration.
+pub struct DbNotesIterator<'a, H: ByteHash> {
+    notes: &'a HAMTMap<u64, NoteVariant, H>,
+    range: Range<u64>,
 }
 
-impl<H: ByteHash> From<&Db<H>> for DbNotesIterator<H> {
-    fn from(db: &Db<H>) -> Self {
-        let notes = db.notes.clone();
-        let cur = 0;
-
-        DbNotesIterator { notes, cur }
+impl<'a, H: ByteHash> From<&'a Db<H>> for DbNotesIterator<'a, H> {
+    fn from(db: &'a Db<H>) -> Self {
+        db.notes_range(..)
     }
 }
 
-impl<H: ByteHash> Iterator for DbNotesIterator<H> {
-    type Item = NoteVariant;
+impl<'a, H: ByteHash> Iterator for DbNotesIterator<'a, H> {
+    type Item = Result<NoteVariant, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let idx = self.cur;
-        self.cur += 1;
+        let idx = self.range.next()?;
 
-        match self.notes.get(&idx) {
-            Ok(n) => n.map(|n| n.clone()),
-            // TODO - Report error
-            Err(_) => None,
-        }
+        let note = self
+            .notes
+            .get(&idx)
+            .map_err(Error::from)
+            .and_then(|n| n.map(|n| n.clone()).ok_or(Error::NotFound));
+
+        Some(note)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
     }
 }
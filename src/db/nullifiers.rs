@@ -0,0 +1,258 @@
+use crate::{crypto, utils, BlsScalar, Error, Nullifier};
+
+use std::io::{self, Read, Write};
+
+use bytehash::ByteHash;
+use kelvin::{Content, Sink, Source};
+use kelvin_hamt::CountingHAMTMap as HAMTMap;
+use poseidon252::StorageScalar;
+use unprolix::Getters;
+
+/// Number of bits of the nullifier that select the child of a node of the nullifiers tree
+const LEVEL_BITS: usize = crypto::ARITY.trailing_zeros() as usize;
+
+/// Height of the sparse merkle tree of the nullifiers. Every nullifier has a fixed leaf, selected
+/// by its bits
+pub const NULLIFIERS_TREE_HEIGHT: usize = utils::BLS_SCALAR_SERIALIZED_SIZE * 8 / LEVEL_BITS;
+
+/// Children of a node of the nullifiers tree
+pub type NullifiersLevel = [Option<BlsScalar>; crypto::ARITY];
+
+/// Size of a [`NodeKey`]: the height, followed by the nullifier bits above that height
+const NODE_KEY_SIZE: usize = 1 + utils::BLS_SCALAR_SERIALIZED_SIZE;
+
+/// Key of a node of the nullifiers tree: its height, and the nullifier bits above that height
+///
+/// The height is part of the key bytes, so the nodes of different heights sharing the same
+/// prefix, such as the root and the nodes on the path of a nullifier with the top bits clear,
+/// never collide on the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct NodeKey {
+    bytes: [u8; NODE_KEY_SIZE],
+}
+
+impl NodeKey {
+    fn new(nullifier: &Nullifier, height: usize) -> Self {
+        let mut bytes = [0x00u8; NODE_KEY_SIZE];
+        bytes[0] = height as u8;
+        bytes[1..].copy_from_slice(&nullifier.to_bytes());
+
+        (0..height * LEVEL_BITS).for_each(|b| bytes[1 + b / 8] &= !(1u8 << (b % 8)));
+
+        Self { bytes }
+    }
+
+    fn height(&self) -> usize {
+        self.bytes[0] as usize
+    }
+
+    /// Key of the child of the provided position
+    fn child(&self, position: usize) -> Self {
+        let height = self.height() - 1;
+        let mut bytes = self.bytes;
+        bytes[0] = height as u8;
+
+        (0..LEVEL_BITS)
+            .filter(|b| position & (1 << b) != 0)
+            .map(|b| height * LEVEL_BITS + b)
+            .for_each(|b| bytes[1 + b / 8] |= 1u8 << (b % 8));
+
+        Self { bytes }
+    }
+}
+
+impl AsRef<[u8]> for NodeKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<H: ByteHash> Content<H> for NodeKey {
+    fn persist(&mut self, sink: &mut Sink<H>) -> io::Result<()> {
+        sink.write_all(&self.bytes)
+    }
+
+    fn restore(source: &mut Source<H>) -> io::Result<Self> {
+        let mut bytes = [0x00u8; NODE_KEY_SIZE];
+        source.read_exact(&mut bytes)?;
+
+        Ok(Self { bytes })
+    }
+}
+
+/// Position of the nullifier path on the children of the node of the provided height
+fn path_position(nullifier: &Nullifier, height: usize) -> usize {
+    let bytes = nullifier.to_bytes();
+
+    (0..LEVEL_BITS)
+        .map(|b| height * LEVEL_BITS + b)
+        .enumerate()
+        .filter(|(_, b)| bytes[b / 8] & (1u8 << (b % 8)) != 0)
+        .fold(0, |position, (i, _)| position | (1 << i))
+}
+
+/// Hash of a node provided its children. A node without children is empty
+fn hash_level(level: NullifiersLevel) -> Option<BlsScalar> {
+    if level.iter().all(Option::is_none) {
+        None
+    } else {
+        Some(crypto::merkle::hash_leaves(level))
+    }
+}
+
+/// Sparse merkle tree that commits to the set of spent nullifiers
+#[derive(Clone)]
+pub(crate) struct NullifiersTree<H: ByteHash> {
+    nodes: HAMTMap<NodeKey, StorageScalar, H>,
+}
+
+impl<H: ByteHash> Default for NullifiersTree<H> {
+    fn default() -> Self {
+        Self {
+            nodes: HAMTMap::default(),
+        }
+    }
+}
+
+impl<H: ByteHash> Content<H> for NullifiersTree<H> {
+    fn persist(&mut self, sink: &mut Sink<H>) -> io::Result<()> {
+        self.nodes.persist(sink)
+    }
+
+    fn restore(source: &mut Source<H>) -> io::Result<Self> {
+        Ok(Self {
+            nodes: HAMTMap::restore(source)?,
+        })
+    }
+}
+
+impl<H: ByteHash> NullifiersTree<H> {
+    /// Root of the tree. The root of the empty tree is zero
+    pub fn root(&self) -> Result<BlsScalar, Error> {
+        let root = NodeKey::new(&Nullifier::default(), NULLIFIERS_TREE_HEIGHT);
+
+        self.node(&root).map(|r| r.unwrap_or_else(BlsScalar::zero))
+    }
+
+    /// Set the leaf of the nullifier, and recalculate the path from the leaf up to the root
+    pub fn insert(&mut self, nullifier: &Nullifier) -> Result<(), Error> {
+        self.nodes
+            .insert(NodeKey::new(nullifier, 0), StorageScalar(BlsScalar::one()))?;
+
+        for height in 1..=NULLIFIERS_TREE_HEIGHT {
+            let key = NodeKey::new(nullifier, height);
+            let node = hash_level(self.level(&key)?).ok_or(Error::NotFound)?;
+
+            self.nodes.insert(key, StorageScalar(node))?;
+        }
+
+        Ok(())
+    }
+
+    /// Clear the leaf of the nullifier, and recalculate the path from the leaf up to the root.
+    ///
+    /// The nodes left without children are removed, so the tree is the same as if the nullifier
+    /// was never inserted.
+    pub fn remove(&mut self, nullifier: &Nullifier) -> Result<(), Error> {
+        self.nodes.remove(&NodeKey::new(nullifier, 0))?;
+
+        for height in 1..=NULLIFIERS_TREE_HEIGHT {
+            let key = NodeKey::new(nullifier, height);
+
+            match hash_level(self.level(&key)?) {
+                Some(node) => {
+                    self.nodes.insert(key, StorageScalar(node))?;
+                }
+                None => {
+                    self.nodes.remove(&key)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create a proof of the presence, or absence, of the nullifier on the tree
+    pub fn proof(&self, nullifier: &Nullifier) -> Result<NullifierProof, Error> {
+        let levels = (1..=NULLIFIERS_TREE_HEIGHT)
+            .map(|height| self.level(&NodeKey::new(nullifier, height)))
+            .collect::<Result<Vec<NullifiersLevel>, Error>>()?;
+
+        Ok(NullifierProof {
+            nullifier: *nullifier,
+            levels,
+        })
+    }
+
+    fn level(&self, key: &NodeKey) -> Result<NullifiersLevel, Error> {
+        let mut level = [None; crypto::ARITY];
+
+        level
+            .iter_mut()
+            .enumerate()
+            .try_for_each::<_, Result<_, Error>>(|(i, l)| {
+                *l = self.node(&key.child(i))?;
+                Ok(())
+            })?;
+
+        Ok(level)
+    }
+
+    fn node(&self, key: &NodeKey) -> Result<Option<BlsScalar>, Error> {
+        Ok(self.nodes.get(key)?.map(|n| n.0))
+    }
+}
+
+/// Proof that a nullifier is, or is not, part of the nullifiers set committed by a root of the
+/// nullifiers tree.
+///
+/// The levels are the children of every node of the nullifier path, from the leaves up to the
+/// root.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct NullifierProof {
+    nullifier: Nullifier,
+    levels: Vec<NullifiersLevel>,
+}
+
+impl NullifierProof {
+    /// Whether the proof claims the nullifier is spent
+    pub fn is_spent(&self) -> bool {
+        self.levels
+            .first()
+            .map(|l| l[path_position(&self.nullifier, 0)].is_some())
+            .unwrap_or(false)
+    }
+
+    /// Verify the proof against a root of the nullifiers tree.
+    ///
+    /// A valid proof proves the nullifier is spent if [`NullifierProof::is_spent`], and unspent
+    /// otherwise.
+    pub fn verify(&self, root: &BlsScalar) -> bool {
+        if self.levels.len() != NULLIFIERS_TREE_HEIGHT {
+            return false;
+        }
+
+        let leaf = if self.is_spent() {
+            Some(BlsScalar::one())
+        } else {
+            None
+        };
+
+        let node = self
+            .levels
+            .iter()
+            .enumerate()
+            .try_fold(leaf, |node, (height, level)| {
+                if level[path_position(&self.nullifier, height)] == node {
+                    Some(hash_level(*level))
+                } else {
+                    None
+                }
+            });
+
+        match node {
+            Some(node) => &node.unwrap_or_else(BlsScalar::zero) == root,
+            None => false,
+        }
+    }
+}
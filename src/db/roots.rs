@@ -0,0 +1,107 @@
+use crate::{utils, BlsScalar};
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use bytehash::ByteHash;
+use kelvin::{Content, Sink, Source};
+
+/// Default number of recent roots of the notes tree accepted for the transaction inputs
+pub const DEFAULT_ROOT_WINDOW: usize = 64;
+
+/// Bounded ring of the most recent roots of the notes tree.
+///
+/// A root is recorded for every transaction or standalone note stored on the [`super::Db`]. When
+/// the window is full, the oldest root is evicted for every new root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootWindow {
+    capacity: usize,
+    roots: VecDeque<BlsScalar>,
+}
+
+impl Default for RootWindow {
+    fn default() -> Self {
+        Self::new(DEFAULT_ROOT_WINDOW)
+    }
+}
+
+impl RootWindow {
+    /// Create a new empty window that keeps at most `capacity` roots
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            roots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Maximum number of roots kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the maximum number of roots kept, evicting the oldest roots if required
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Replace the roots with the ones of the provided window, keeping the current capacity and
+    /// evicting the oldest roots if required
+    pub fn restore_roots(&mut self, window: RootWindow) {
+        self.roots = window.roots;
+        self.evict();
+    }
+
+    /// Append a new root, evicting the oldest if the window is full
+    pub fn push(&mut self, root: BlsScalar) {
+        if self.roots.back() != Some(&root) {
+            self.roots.push_back(root);
+            self.evict();
+        }
+    }
+
+    /// Check if the root is in the window
+    pub fn contains(&self, root: &BlsScalar) -> bool {
+        self.roots.contains(root)
+    }
+
+    /// Roots of the window, from the oldest to the most recent
+    pub fn iter(&self) -> impl Iterator<Item = &BlsScalar> {
+        self.roots.iter()
+    }
+
+    fn evict(&mut self) {
+        while self.roots.len() > self.capacity {
+            self.roots.pop_front();
+        }
+    }
+}
+
+impl<H: ByteHash> Content<H> for RootWindow {
+    fn persist(&mut self, sink: &mut Sink<H>) -> io::Result<()> {
+        (self.capacity as u64).persist(sink)?;
+        (self.roots.len() as u64).persist(sink)?;
+
+        self.roots
+            .iter()
+            .try_for_each(|root| sink.write_all(&root.to_bytes()[..]))
+    }
+
+    fn restore(source: &mut Source<H>) -> io::Result<Self> {
+        let capacity = u64::restore(source)? as usize;
+        let len = u64::restore(source)? as usize;
+
+        if len > capacity {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The root window exceeds its capacity",
+            ));
+        }
+
+        let roots = (0..len)
+            .map(|_| utils::kelvin_source_to_bls_scalar(source))
+            .collect::<io::Result<VecDeque<BlsScalar>>>()?;
+
+        Ok(Self { capacity, roots })
+    }
+}
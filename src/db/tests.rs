@@ -1,9 +1,16 @@
 use crate::{
-    db, MerkleProofProvider, Note, NoteGenerator, NoteVariant, ObfuscatedNote, SecretKey,
-    Transaction, TransparentNote,
+    crypto, db, BlsScalar, Error, MerkleProofProvider, Note, NoteGenerator, NoteVariant, NotesDb,
+    NotesIter, Nullifier, ObfuscatedNote, SecretKey, Transaction, TransactionBuilder,
+    TransparentNote, ValidationError,
 };
 
-use kelvin::Blake2b;
+use std::fs;
+
+use kelvin::{
+    tests::{arbitrary as a, fuzz_content_iterations},
+    Blake2b,
+};
+use tempdir::TempDir;
 
 #[test]
 fn transparent_note_serialization() {
@@ -59,6 +66,36 @@ fn obfuscated_note_serialization() {
     assert_eq!(blinding_factor, db_note.blinding_factor(Some(&vk)).unwrap());
 }
 
+#[test]
+fn merkle_opening() {
+    let mut db = db::Db::<Blake2b>::default();
+    let pk = SecretKey::default().public_key();
+
+    let mut root = db.root().unwrap();
+    let idx: Vec<u64> = (0..crypto::ARITY as u64 * 3 + 1)
+        .map(|value| {
+            let note: NoteVariant = ObfuscatedNote::output(&pk, value).0.into();
+            let idx = db.store_unspent_note(note).unwrap();
+
+            let new_root = db.root().unwrap();
+            assert_ne!(root, new_root);
+            root = new_root;
+
+            idx
+        })
+        .collect();
+
+    for i in idx {
+        let note = db.fetch_note(i).unwrap();
+        let opening = db.opening(&note).unwrap();
+        let leaf = opening.levels()[0];
+
+        assert!(opening.verify());
+        assert_eq!(&root, opening.root());
+        assert_eq!(note.hash(), leaf.data()[leaf.idx() + 1]);
+    }
+}
+
 #[test]
 #[ignore]
 fn double_spending() {
@@ -185,3 +222,464 @@ fn double_spending() {
         .store_bulk_transactions(&[tx_ok, tx_double_spending])
         .is_err());
 }
+
+fn unspent_transaction(db: &mut db::Db<Blake2b>, value: u64) -> Transaction {
+    let sk = SecretKey::default();
+    let note: NoteVariant = ObfuscatedNote::output(&sk.public_key(), value).0.into();
+    let idx = db.store_unspent_note(note).unwrap();
+
+    TransactionBuilder::new(sk)
+        .notes(vec![db.fetch_note(idx).unwrap()])
+        .output(SecretKey::default().public_key(), value - 1)
+        .fee(1)
+        .build(db)
+        .unwrap()
+}
+
+#[test]
+fn validate_transaction() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let mut tx = unspent_transaction(&mut db, 100);
+    match db.validate_transaction(&tx) {
+        Err(ValidationError::InvalidProof) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+
+    tx.prove().unwrap();
+
+    let count = db.notes_count();
+    let root = db.root().unwrap();
+    db.validate_transaction(&tx).unwrap();
+    assert_eq!(count, db.notes_count());
+    assert_eq!(root, db.root().unwrap());
+
+    db.store_transaction(&tx).unwrap();
+    assert_ne!(root, db.root().unwrap());
+    match db.validate_transaction(&tx) {
+        Err(ValidationError::DoubleSpending(0)) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+
+    let mut tx = unspent_transaction(&mut db, 100);
+    tx.prove().unwrap();
+    db.store_transaction(&tx).unwrap();
+
+    // Spend the nullifier again against the current root
+    let mut double_spending = Transaction::default();
+    let mut input = tx.inputs()[0];
+    input.merkle_root = db.root().unwrap();
+    double_spending.push_input(input).unwrap();
+    match db.validate_transaction(&double_spending) {
+        Err(ValidationError::DoubleSpending(0)) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+}
+
+#[test]
+fn validate_transaction_malformed() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    match db.validate_transaction(&Transaction::default()) {
+        Err(ValidationError::InvalidShape) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+
+    let tx = unspent_transaction(&mut db, 100);
+
+    let mut duplicated = tx.clone();
+    duplicated.push_input(tx.inputs()[0]).unwrap();
+    match db.validate_transaction(&duplicated) {
+        Err(ValidationError::DuplicatedNullifier(1)) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+
+    let mut obfuscated_fee = tx.clone();
+    let pk = SecretKey::default().public_key();
+    let (note, blinding_factor) = ObfuscatedNote::output(&pk, 1);
+    obfuscated_fee.set_fee(note.to_transaction_output(1, blinding_factor, pk));
+    match db.validate_transaction(&obfuscated_fee) {
+        Err(ValidationError::InvalidFee) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+}
+
+#[test]
+fn root_window() {
+    let mut db = db::Db::<Blake2b>::with_root_window(3);
+    let pk = SecretKey::default().public_key();
+
+    let roots: Vec<BlsScalar> = (0..5)
+        .map(|value| {
+            let note: NoteVariant = ObfuscatedNote::output(&pk, value).0.into();
+            db.store_unspent_note(note).unwrap();
+            db.root().unwrap()
+        })
+        .collect();
+
+    assert_eq!(3, db.root_window().iter().count());
+    assert!(!db.is_known_root(&roots[1]).unwrap());
+    roots[2..]
+        .iter()
+        .for_each(|r| assert!(db.is_known_root(r).unwrap()));
+
+    let mut tx = unspent_transaction(&mut db, 100);
+    tx.prove().unwrap();
+    let old_root = tx.inputs()[0].merkle_root;
+
+    // Every stored transaction records a single root, regardless of its number of notes
+    let other = unspent_transaction(&mut db, 100);
+    db.store_transaction(&other).unwrap();
+    assert!(db.is_known_root(&old_root).unwrap());
+    db.validate_transaction(&tx).unwrap();
+
+    db.set_root_window(1);
+    assert!(!db.is_known_root(&old_root).unwrap());
+    match db.validate_transaction(&tx) {
+        Err(ValidationError::UnknownMerkleRoot(0)) => (),
+        r => panic!("Unexpected validation result {:?}", r),
+    }
+
+    let e = db.validate_transaction(&tx).unwrap_err();
+    assert_eq!("Unknown merkle root of input 0", format!("{}", e));
+    match Error::from(e) {
+        Error::UnknownMerkleRoot(0) => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn root_window_content() {
+    impl a::Arbitrary for db::RootWindow {
+        fn arbitrary(u: &mut a::Unstructured<'_>) -> Result<Self, a::Error> {
+            let capacity: u8 = a::Arbitrary::arbitrary(u)?;
+            let roots: Vec<u64> = a::Arbitrary::arbitrary(u)?;
+
+            let mut window = db::RootWindow::new(capacity as usize);
+            roots
+                .into_iter()
+                .for_each(|r| window.push(BlsScalar::from(r)));
+
+            Ok(window)
+        }
+    }
+
+    fuzz_content_iterations::<db::RootWindow, Blake2b>(64);
+}
+
+#[test]
+fn store_block_atomic() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let tx = unspent_transaction(&mut db, 100);
+    let other = unspent_transaction(&mut db, 50);
+
+    let count = db.notes_count();
+    let root = db.root().unwrap();
+
+    assert!(db
+        .store_bulk_transactions(&[other.clone(), tx.clone(), tx.clone()])
+        .is_err());
+    assert_eq!(count, db.notes_count());
+    assert_eq!(root, db.root().unwrap());
+    assert_eq!(0, db.revertible_blocks());
+    assert!(db
+        .fetch_nullifier(other.inputs()[0].nullifier())
+        .unwrap()
+        .is_none());
+
+    // A transaction spending the same nullifier twice is rejected as a whole
+    let mut duplicated = tx.clone();
+    duplicated.push_input(tx.inputs()[0]).unwrap();
+    assert!(db.store_transaction(&duplicated).is_err());
+    assert!(db
+        .fetch_nullifier(tx.inputs()[0].nullifier())
+        .unwrap()
+        .is_none());
+
+    let idx = db.store_bulk_transactions(&[other, tx]).unwrap();
+    assert_eq!(count + idx.len() as u64, db.notes_count());
+    assert_eq!(1, db.revertible_blocks());
+}
+
+#[test]
+fn revert_block() {
+    let mut db = db::Db::<Blake2b>::default();
+    assert!(db.revert_block().is_err());
+
+    let tx = unspent_transaction(&mut db, 100);
+    let count = db.notes_count();
+    let root = db.root().unwrap();
+    let nullifiers_root = db.nullifiers_root().unwrap();
+
+    db.store_bulk_transactions(&[tx.clone()]).unwrap();
+    let other = unspent_transaction(&mut db, 50);
+    db.store_bulk_transactions(&[other.clone()]).unwrap();
+    assert_eq!(2, db.revertible_blocks());
+
+    db.revert_block().unwrap();
+    assert!(db
+        .fetch_nullifier(other.inputs()[0].nullifier())
+        .unwrap()
+        .is_none());
+    assert!(db
+        .fetch_nullifier(tx.inputs()[0].nullifier())
+        .unwrap()
+        .is_some());
+
+    db.revert_block().unwrap();
+    assert_eq!(count, db.notes_count());
+    assert_eq!(root, db.root().unwrap());
+    assert_eq!(root, *db.root_window().iter().last().unwrap());
+    assert!(db
+        .fetch_nullifier(tx.inputs()[0].nullifier())
+        .unwrap()
+        .is_none());
+    assert!(db.fetch_note(count).is_err());
+    assert_eq!(nullifiers_root, db.nullifiers_root().unwrap());
+    assert!(db.revert_block().is_err());
+
+    // The reverted block can be applied again
+    db.store_bulk_transactions(&[tx]).unwrap();
+    assert_eq!(1, db.revertible_blocks());
+
+    // Only the undo logs of the most recent blocks are kept
+    (0..db::MAX_REVERTIBLE_BLOCKS).for_each(|_| {
+        db.store_bulk_transactions(&[]).unwrap();
+    });
+    assert_eq!(db::MAX_REVERTIBLE_BLOCKS, db.revertible_blocks());
+}
+
+#[test]
+fn revert_block_outside_changes() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let tx = unspent_transaction(&mut db, 100);
+    db.store_bulk_transactions(&[tx.clone()]).unwrap();
+
+    // A transaction stored outside of a block doesn't extend the undo log of the last block
+    let standalone = unspent_transaction(&mut db, 50);
+    db.store_transaction(&standalone).unwrap();
+
+    // The capacity of the window is a setting, not a part of the block
+    db.set_root_window(3);
+
+    db.revert_block().unwrap();
+    assert!(db
+        .fetch_nullifier(tx.inputs()[0].nullifier())
+        .unwrap()
+        .is_none());
+    assert!(db
+        .fetch_nullifier(standalone.inputs()[0].nullifier())
+        .unwrap()
+        .is_some());
+    assert_eq!(3, db.root_window().capacity());
+    assert!(db.root_window().iter().count() <= 3);
+}
+
+#[test]
+fn notes_db_persistence() {
+    let dir = TempDir::new("notes_db_persistence").unwrap();
+
+    let mut db = NotesDb::open(dir.path()).unwrap();
+    assert_eq!(0, db.notes_count());
+
+    let tx = unspent_transaction(&mut db, 100);
+    db.store_bulk_transactions(&[tx.clone()]).unwrap();
+    let count = db.notes_count();
+    let root = db.root().unwrap();
+    let commit = db.commit().unwrap();
+
+    unspent_transaction(&mut db, 50);
+    let last_commit = db.commit().unwrap();
+    assert_ne!(commit, last_commit);
+    let last_root = db.root().unwrap();
+    drop(db);
+
+    let mut db = NotesDb::open(dir.path()).unwrap();
+    assert_eq!(count + 1, db.notes_count());
+    assert_eq!(last_root, db.root().unwrap());
+
+    let mut db_commit = NotesDb::restore(dir.path(), &commit).unwrap();
+    assert_eq!(count, db_commit.notes_count());
+    assert_eq!(root, db_commit.root().unwrap());
+    assert!(db_commit
+        .fetch_nullifier(tx.inputs()[0].nullifier())
+        .unwrap()
+        .is_some());
+    let note = db_commit.fetch_note(count - 1).unwrap();
+    assert!(db_commit.opening(&note).unwrap().verify());
+
+    // The history of blocks is persisted too
+    db_commit.revert_block().unwrap();
+    assert!(db_commit
+        .fetch_nullifier(tx.inputs()[0].nullifier())
+        .unwrap()
+        .is_none());
+
+    db.revert_block().unwrap();
+    db.commit().unwrap();
+    let db = NotesDb::open(dir.path()).unwrap();
+    assert_eq!(0, db.revertible_blocks());
+
+    assert!(NotesDb::default().commit().is_err());
+}
+
+#[test]
+fn notes_db_interrupted_commit() {
+    let dir = TempDir::new("notes_db_interrupted_commit").unwrap();
+
+    let mut db = NotesDb::open(dir.path()).unwrap();
+    unspent_transaction(&mut db, 100);
+    db.commit().unwrap();
+    let count = db.notes_count();
+    let root = db.root().unwrap();
+
+    // Changes that were not committed are lost
+    unspent_transaction(&mut db, 50);
+    drop(db);
+
+    // A commit interrupted before the root file was replaced
+    let root_file = dir.path().join(db::ROOT_FILE);
+    fs::write(root_file.with_extension("tmp"), b"interrupted").unwrap();
+
+    let mut db = NotesDb::open(dir.path()).unwrap();
+    assert_eq!(count, db.notes_count());
+    assert_eq!(root, db.root().unwrap());
+
+    // The db is still usable after the interruption
+    unspent_transaction(&mut db, 50);
+    let commit = db.commit().unwrap();
+    let db = NotesDb::open(dir.path()).unwrap();
+    assert_eq!(count + 1, db.notes_count());
+
+    fs::write(&root_file, b"corrupted").unwrap();
+    match NotesDb::open(dir.path()).err().unwrap() {
+        Error::InvalidEncoding(db::ROOT_FILE) => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    // A root file pointing at a root missing from the store
+    let missing = vec![0xfau8; commit.as_ref().len()];
+    fs::write(&root_file, hex::encode(missing)).unwrap();
+    assert!(NotesDb::open(dir.path()).is_err());
+
+    // A partially written root file
+    let partial = hex::encode(commit.as_ref());
+    fs::write(&root_file, &partial[..partial.len() / 2]).unwrap();
+    match NotesDb::open(dir.path()).err().unwrap() {
+        Error::InvalidLength {
+            field: db::ROOT_FILE,
+            expected,
+            actual,
+        } if expected == commit.as_ref().len() && actual == expected / 2 => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    fs::write(&root_file, hex::encode(commit.as_ref())).unwrap();
+    assert_eq!(count + 1, NotesDb::open(dir.path()).unwrap().notes_count());
+}
+
+#[test]
+fn notes_iterator() {
+    let mut db = db::Db::<Blake2b>::default();
+    let pk = SecretKey::default().public_key();
+
+    let notes: Vec<NoteVariant> = (0..5)
+        .map(|value| {
+            let note: NoteVariant = ObfuscatedNote::output(&pk, value).0.into();
+            let idx = db.store_unspent_note(note).unwrap();
+
+            db.fetch_note(idx).unwrap()
+        })
+        .collect();
+
+    let all = NotesIter::from(&db)
+        .collect::<Result<Vec<NoteVariant>, _>>()
+        .unwrap();
+    assert_eq!(notes, all);
+
+    let range = db
+        .notes_range(1..3)
+        .collect::<Result<Vec<NoteVariant>, _>>()
+        .unwrap();
+    assert_eq!(&notes[1..3], range.as_slice());
+
+    let from = db
+        .notes_from(3)
+        .collect::<Result<Vec<NoteVariant>, _>>()
+        .unwrap();
+    assert_eq!(&notes[3..], from.as_slice());
+
+    assert_eq!(2, db.notes_range(3..=10).count());
+    assert_eq!(0, db.notes_from(10).count());
+
+    fn is_send<T: Send>(_: &T) {}
+    is_send(&db.notes_from(0));
+}
+
+#[test]
+fn nullifier_proofs() {
+    let mut db = db::Db::<Blake2b>::default();
+    assert_eq!(BlsScalar::zero(), db.nullifiers_root().unwrap());
+
+    let tx = unspent_transaction(&mut db, 100);
+    let spent = *tx.inputs()[0].nullifier();
+    let unspent = Nullifier::from(BlsScalar::from(12345u64));
+
+    let empty_root = db.nullifiers_root().unwrap();
+    let proof_before = db.nullifier_proof(&spent).unwrap();
+    assert!(!proof_before.is_spent());
+    assert!(proof_before.verify(&empty_root));
+
+    db.store_transaction(&tx).unwrap();
+    let root = db.nullifiers_root().unwrap();
+    assert_ne!(empty_root, root);
+
+    assert_eq!(
+        vec![Some(()), None],
+        db.fetch_nullifiers(&[spent, unspent]).unwrap()
+    );
+
+    let proof = db.nullifier_proof(&spent).unwrap();
+    assert!(proof.is_spent());
+    assert!(proof.verify(&root));
+    assert!(!proof.verify(&empty_root));
+    assert!(!proof_before.verify(&root));
+
+    let proof = db.nullifier_proof(&unspent).unwrap();
+    assert!(!proof.is_spent());
+    assert!(proof.verify(&root));
+}
+
+#[test]
+fn nullifier_proofs_top_bits_clear() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    // The nodes on the path of a nullifier with the top bits clear share their prefix with the
+    // root, so they collide with it unless the height is part of the key
+    let nullifiers: Vec<Nullifier> = (1..4u64)
+        .map(|i| Nullifier::from(BlsScalar::from(i)))
+        .collect();
+
+    for n in nullifiers.iter() {
+        db.nullifiers_tree.insert(n).unwrap();
+
+        let root = db.nullifiers_root().unwrap();
+        let proof = db.nullifier_proof(n).unwrap();
+        assert!(proof.is_spent());
+        assert!(proof.verify(&root));
+    }
+
+    let root = db.nullifiers_root().unwrap();
+    let proof = db
+        .nullifier_proof(&Nullifier::from(BlsScalar::from(4u64)))
+        .unwrap();
+    assert!(!proof.is_spent());
+    assert!(proof.verify(&root));
+
+    for n in nullifiers.iter() {
+        db.nullifiers_tree.remove(n).unwrap();
+    }
+    assert_eq!(BlsScalar::zero(), db.nullifiers_root().unwrap());
+}
@@ -0,0 +1,50 @@
+use super::RootWindow;
+use crate::{utils, Nullifier};
+
+use std::io::{self, Write};
+
+use bytehash::ByteHash;
+use kelvin::{Content, Sink, Source};
+
+/// Changes of a block stored with [`super::Db::store_bulk_transactions`], so the block can be
+/// undone with [`super::Db::revert_block`].
+///
+/// The notes tree and the nullifiers tree are functions of their leaves, so only the first
+/// position of the block and the spent nullifiers are recorded. The root window is bounded, so
+/// it is copied as a whole.
+#[derive(Debug, Clone)]
+pub(crate) struct BlockUndo {
+    pub notes_count: u64,
+    pub nullifiers: Vec<Nullifier>,
+    pub roots: RootWindow,
+}
+
+impl<H: ByteHash> Content<H> for BlockUndo {
+    fn persist(&mut self, sink: &mut Sink<H>) -> io::Result<()> {
+        self.notes_count.persist(sink)?;
+        (self.nullifiers.len() as u64).persist(sink)?;
+
+        self.nullifiers
+            .iter()
+            .try_for_each(|n| sink.write_all(&n.to_bytes()[..]))?;
+
+        self.roots.persist(sink)
+    }
+
+    fn restore(source: &mut Source<H>) -> io::Result<Self> {
+        let notes_count = u64::restore(source)?;
+        let len = u64::restore(source)?;
+
+        let nullifiers = (0..len)
+            .map(|_| utils::kelvin_source_to_bls_scalar(source).map(Nullifier::from))
+            .collect::<io::Result<Vec<Nullifier>>>()?;
+
+        let roots = RootWindow::restore(source)?;
+
+        Ok(Self {
+            notes_count,
+            nullifiers,
+            roots,
+        })
+    }
+}
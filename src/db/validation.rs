@@ -0,0 +1,72 @@
+use crate::Error;
+
+use std::{error, fmt};
+
+/// Reason of a transaction rejected by [`super::Db::validate_transaction`]
+///
+/// The input positions refer to the order of [`crate::Transaction::inputs`]
+#[derive(Debug)]
+pub enum ValidationError {
+    /// The transaction has no inputs, or exceeds the maximum number of inputs or outputs
+    InvalidShape,
+    /// The fee output is not a transparent note
+    InvalidFee,
+    /// The merkle root of the input is not a root of the notes tree
+    UnknownMerkleRoot(usize),
+    /// The nullifier of the input is repeated on the transaction
+    DuplicatedNullifier(usize),
+    /// The nullifier of the input was already spent
+    DoubleSpending(usize),
+    /// The transaction proof is missing or doesn't verify against its public inputs
+    InvalidProof,
+    /// The storage failed to answer a query
+    Storage(Error),
+}
+
+impl From<Error> for ValidationError {
+    fn from(e: Error) -> Self {
+        ValidationError::Storage(e)
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        match e {
+            ValidationError::DuplicatedNullifier(_) | ValidationError::DoubleSpending(_) => {
+                Error::DoubleSpending
+            }
+            ValidationError::InvalidShape => Error::InvalidShape,
+            ValidationError::InvalidFee => Error::FeeOutput,
+            ValidationError::UnknownMerkleRoot(i) => Error::UnknownMerkleRoot(i),
+            ValidationError::InvalidProof => Error::InvalidProof,
+            ValidationError::Storage(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::InvalidShape => write!(f, "Invalid number of inputs or outputs"),
+            ValidationError::InvalidFee => write!(f, "The fee is not a transparent note"),
+            ValidationError::UnknownMerkleRoot(i) => {
+                write!(f, "Unknown merkle root of input {}", i)
+            }
+            ValidationError::DuplicatedNullifier(i) => {
+                write!(f, "Duplicated nullifier of input {}", i)
+            }
+            ValidationError::DoubleSpending(i) => write!(f, "Input {} is already spent", i),
+            ValidationError::InvalidProof => write!(f, "Invalid transaction proof"),
+            ValidationError::Storage(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ValidationError::Storage(e) => Some(e),
+            _ => None,
+        }
+    }
+}
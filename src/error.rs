@@ -19,10 +19,38 @@ pub enum Error {
     Io(io::Error),
     /// Fmt [`fmt::Error`]
     Fmt(fmt::Error),
+    /// Bincode [`bincode::Error`]
+    Bincode(bincode::Error),
+    /// Protobuf [`prost::DecodeError`]
+    Prost(prost::DecodeError),
     /// Field operation error
     Field(String),
-    /// Cryptographic bottom
-    Generic,
+    /// Failure of a dependency that doesn't fit any other variant
+    Other(Box<dyn error::Error + Send + Sync>),
+    /// A field of a structure could not be decoded, because of the source error
+    Decode {
+        /// Name of the field, as `Type.field`
+        field: &'static str,
+        /// Reason of the failure
+        source: Box<Error>,
+    },
+    /// A required field of a RPC structure is missing
+    MissingField(&'static str),
+    /// A field or buffer doesn't have the expected length
+    InvalidLength {
+        /// Name of the decoded item
+        field: &'static str,
+        /// Required length
+        expected: usize,
+        /// Provided length
+        actual: usize,
+    },
+    /// The bytes are not the canonical encoding of the item
+    InvalidEncoding(&'static str),
+    /// The proof is missing or doesn't verify against its public inputs
+    InvalidProof,
+    /// A required configuration entry, such as an environment variable, is not set
+    MissingConfiguration(&'static str),
     /// Resource not ready
     NotReady,
     /// The transaction needs to be prepared before it can be stored
@@ -35,16 +63,75 @@ pub enum Error {
     InvalidParameters,
     /// Maximum number of notes per transaction exceeded
     MaximumNotes,
+    /// The transaction has no inputs, or exceeds the maximum number of inputs or outputs
+    InvalidShape,
+    /// The merkle root of the input with the provided position is not a root of the notes tree
+    UnknownMerkleRoot(usize),
     /// The queried information was not found
     NotFound,
     /// Attempt to double spend
     DoubleSpending,
+    /// The available notes don't cover the requested value
+    InsufficientFunds,
+    /// A sum of note values doesn't fit a `u64`
+    ValueOverflow,
+    /// The mnemonic phrase contains unknown words or an invalid number of words
+    InvalidMnemonic,
+    /// The checksum of the mnemonic phrase doesn't match its entropy
+    InvalidMnemonicChecksum,
+    /// The encrypted data could not be authenticated with the provided key
+    DecryptionFailed,
+    /// The nullifiers of the owned notes can't be generated without the secret key
+    SecretKeyRequired,
+    /// Positions of the invalid items of a batch verification
+    InvalidBatch(Vec<usize>),
 }
 
+/// Failure of a dependency whose error type doesn't implement [`error::Error`].
+///
+/// The original value is kept, so it can be recovered by downcasting the source of
+/// [`Error::Other`].
+#[derive(Debug)]
+pub struct DebugError<T>(pub T);
+
+impl<T: fmt::Debug> fmt::Display for DebugError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl<T: fmt::Debug> error::Error for DebugError<T> {}
+
 impl Error {
-    /// Return a generic error from any type. Represents a cryptographic bottom
-    pub fn generic<T>(_e: T) -> Error {
-        Error::Generic
+    /// Return an error from a type that doesn't implement [`error::Error`], keeping it as the
+    /// [`DebugError`] source
+    pub fn generic<T: fmt::Debug + Send + Sync + 'static>(e: T) -> Error {
+        Error::Other(Box::new(DebugError(e)))
+    }
+
+    /// Return an error that keeps the provided error as its source
+    pub fn other<E: Into<Box<dyn error::Error + Send + Sync>>>(e: E) -> Error {
+        Error::Other(e.into())
+    }
+
+    /// Wrap the error as the reason why the provided field could not be decoded
+    pub fn context(self, field: &'static str) -> Error {
+        Error::Decode {
+            field,
+            source: Box::new(self),
+        }
+    }
+}
+
+/// Attach the decoded field to the errors of a result
+pub trait ResultExt<T> {
+    /// Wrap the error, if any, as the reason why the provided field could not be decoded
+    fn context(self, field: &'static str) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn context(self, field: &'static str) -> Result<T, Error> {
+        self.map_err(|e| e.into().context(field))
     }
 }
 
@@ -53,9 +140,41 @@ impl fmt::Display for Error {
         match self {
             Error::Io(e) => write!(f, "{}", e),
             Error::Fmt(e) => write!(f, "{}", e),
-            //Error::R1CS(e) => write!(f, "{}", e),
+            Error::Bincode(e) => write!(f, "{}", e),
+            Error::Prost(e) => write!(f, "{}", e),
             Error::Field(s) => write!(f, "{}", s),
-            _ => write!(f, "{:?}", self),
+            Error::Other(e) => write!(f, "{}", e),
+            Error::Decode { field, source } => write!(f, "Invalid {}: {}", field, source),
+            Error::MissingField(field) => write!(f, "Missing {}", field),
+            Error::InvalidLength {
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Invalid length of {}: expected {}, got {}",
+                field, expected, actual
+            ),
+            Error::InvalidEncoding(field) => write!(f, "Invalid encoding of {}", field),
+            Error::MissingConfiguration(var) => write!(f, "{} is not set", var),
+            Error::ValueOverflow => write!(f, "The sum of the note values overflows"),
+            Error::InvalidProof => write!(f, "The proof is missing or invalid"),
+            Error::NotReady => write!(f, "The resource is not ready"),
+            Error::TransactionNotPrepared => write!(f, "The transaction is not prepared"),
+            Error::FeeOutput => write!(f, "Failed to create the fee output"),
+            Error::InvalidPoint => write!(f, "Invalid compressed point"),
+            Error::InvalidParameters => write!(f, "Invalid parameters"),
+            Error::MaximumNotes => write!(f, "Maximum number of notes per transaction exceeded"),
+            Error::InvalidShape => write!(f, "Invalid number of inputs or outputs"),
+            Error::UnknownMerkleRoot(i) => write!(f, "Unknown merkle root of input {}", i),
+            Error::NotFound => write!(f, "Not found"),
+            Error::DoubleSpending => write!(f, "Double spending attempt"),
+            Error::InsufficientFunds => write!(f, "Insufficient funds"),
+            Error::InvalidMnemonic => write!(f, "Invalid mnemonic phrase"),
+            Error::InvalidMnemonicChecksum => write!(f, "Invalid mnemonic checksum"),
+            Error::DecryptionFailed => write!(f, "Decryption failed"),
+            Error::SecretKeyRequired => write!(f, "The secret key is required"),
+            Error::InvalidBatch(invalid) => write!(f, "Invalid batch items {:?}", invalid),
         }
     }
 }
@@ -65,6 +184,10 @@ impl error::Error for Error {
         match self {
             Error::Io(e) => Some(e),
             Error::Fmt(e) => Some(e),
+            Error::Bincode(e) => Some(e),
+            Error::Prost(e) => Some(e),
+            Error::Other(e) => Some(e.as_ref()),
+            Error::Decode { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
@@ -74,7 +197,7 @@ impl Into<io::Error> for Error {
     fn into(self) -> io::Error {
         match self {
             Error::Io(e) => e,
-            _ => io::Error::new(io::ErrorKind::Other, format!("{}", self)),
+            _ => io::Error::new(io::ErrorKind::Other, self),
         }
     }
 }
@@ -85,12 +208,7 @@ impl Into<fmt::Error> for Error {
     }
 }
 
-impl From<Error> for tonic::Status {
-    fn from(e: Error) -> Self {
-        // TODO - Improve the error mapping to tonic codes
-        tonic::Status::new(tonic::Code::Internal, format!("{}", e))
-    }
-}
-
 from_error!(io::Error, Io);
 from_error!(fmt::Error, Fmt);
+from_error!(bincode::Error, Bincode);
+from_error!(prost::DecodeError, Prost);
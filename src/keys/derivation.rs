@@ -0,0 +1,243 @@
+use crate::{crypto, BlsScalar, Error, JubJubScalar, PublicKey, SecretKey, ViewKey};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use blake2::{Blake2b, Digest};
+
+/// Domain separation of the master key from the seed
+const MASTER_DOMAIN: u64 = 0x6d6173746572;
+/// Domain separation of the key material of a child
+const KEY_DOMAIN: u64 = 0x01;
+/// Domain separation of the chain code of a child
+const CHAIN_DOMAIN: u64 = 0x02;
+/// Domain separation of the `a` secret of a [`SecretKey`]
+const SECRET_A_DOMAIN: u64 = 0x03;
+/// Domain separation of the `b` secret of a [`SecretKey`]
+const SECRET_B_DOMAIN: u64 = 0x04;
+
+/// Minimum size of the seed of a master key, as in BIP32
+pub const MIN_SEED_SIZE: usize = 16;
+
+/// Number of bytes of the seed packed in every [`BlsScalar`], so the packed value is always
+/// below the modulus
+const SEED_CHUNK_SIZE: usize = 31;
+
+/// Path of a derived key from the master, represented as `m/account/index`
+///
+/// Every step of the path is hardened, so the `'` suffix is accepted but optional when parsing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DerivationPath {
+    indexes: Vec<u32>,
+}
+
+impl DerivationPath {
+    /// Path of the keys of an account, `m/account`
+    pub fn account(account: u32) -> Self {
+        Self {
+            indexes: vec![account],
+        }
+    }
+
+    /// Path of an indexed key of an account, `m/account/index`
+    pub fn address(account: u32, index: u32) -> Self {
+        Self {
+            indexes: vec![account, index],
+        }
+    }
+
+    /// Append a child index to the path
+    pub fn child(mut self, index: u32) -> Self {
+        self.indexes.push(index);
+        self
+    }
+
+    /// Child indexes from the master
+    pub fn indexes(&self) -> &[u32] {
+        self.indexes.as_slice()
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = s.split('/');
+
+        if steps.next() != Some("m") {
+            return Err(Error::InvalidEncoding("DerivationPath"));
+        }
+
+        let indexes = steps
+            .map(|step| {
+                step.trim_end_matches('\'')
+                    .parse::<u32>()
+                    .map_err(|_| Error::InvalidEncoding("DerivationPath.indexes"))
+            })
+            .collect::<Result<Vec<u32>, Error>>()?;
+
+        Ok(Self { indexes })
+    }
+}
+
+impl TryFrom<&str> for DerivationPath {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+
+        for index in self.indexes.iter() {
+            write!(f, "/{}'", index)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Hierarchical deterministic key, composed of key material and a chain code
+///
+/// The derivation follows the structure of BIP32 hardened derivation, with the HMAC replaced by
+/// the poseidon sponge hash. The master is created from a seed, and every child is created from
+/// `H(chain code, key, index, domain)`.
+///
+/// Every derived key is a complete [`SecretKey`], so every account has its own [`ViewKey`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedSecretKey {
+    key: BlsScalar,
+    chain_code: BlsScalar,
+    depth: u8,
+}
+
+impl ExtendedSecretKey {
+    /// Create the master key from a seed of at least [`MIN_SEED_SIZE`] bytes
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        if seed.len() < MIN_SEED_SIZE {
+            return Err(Error::InvalidLength {
+                field: "ExtendedSecretKey.seed",
+                expected: MIN_SEED_SIZE,
+                actual: seed.len(),
+            });
+        }
+
+        let mut input = vec![
+            BlsScalar::from(MASTER_DOMAIN),
+            BlsScalar::from(seed.len() as u64),
+        ];
+
+        input.extend(seed.chunks(SEED_CHUNK_SIZE).map(|chunk| {
+            let mut bytes = [0x00u8; 32];
+            bytes[0..chunk.len()].copy_from_slice(chunk);
+
+            // Infallible since the most significant byte is always zero
+            BlsScalar::from_bytes(&bytes).unwrap()
+        }));
+
+        let key = crypto::sponge_hash(&input);
+        let chain_code = crypto::sponge_hash(&[BlsScalar::from(CHAIN_DOMAIN), key]);
+
+        Ok(Self {
+            key,
+            chain_code,
+            depth: 0,
+        })
+    }
+
+    /// Derive the hardened child of the provided index
+    pub fn child(&self, index: u32) -> Result<Self, Error> {
+        let depth = self.depth.checked_add(1).ok_or(Error::InvalidLength {
+            field: "DerivationPath.indexes",
+            expected: u8::MAX as usize,
+            actual: u8::MAX as usize + 1,
+        })?;
+        let index = BlsScalar::from(index as u64);
+
+        let key = crypto::sponge_hash(&[
+            self.chain_code,
+            self.key,
+            index,
+            BlsScalar::from(KEY_DOMAIN),
+        ]);
+        let chain_code = crypto::sponge_hash(&[
+            self.chain_code,
+            self.key,
+            index,
+            BlsScalar::from(CHAIN_DOMAIN),
+        ]);
+
+        Ok(Self {
+            key,
+            chain_code,
+            depth,
+        })
+    }
+
+    /// Derive all the children of the path, starting from this key
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, Error> {
+        path.indexes()
+            .iter()
+            .try_fold(*self, |key, index| key.child(*index))
+    }
+
+    /// Derive the key of an account, `m/account`
+    pub fn account(&self, account: u32) -> Result<Self, Error> {
+        self.derive(&DerivationPath::account(account))
+    }
+
+    /// Number of derivations from the master
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Deterministically create the [`SecretKey`] of this node
+    pub fn secret_key(&self) -> SecretKey {
+        derive_secret_key(&self.key)
+    }
+
+    /// Deterministically create the [`ViewKey`] of this node
+    pub fn view_key(&self) -> ViewKey {
+        self.secret_key().view_key()
+    }
+
+    /// Deterministically create the [`PublicKey`] of this node
+    pub fn public_key(&self) -> PublicKey {
+        self.secret_key().public_key()
+    }
+}
+
+impl fmt::Debug for ExtendedSecretKey {
+    /// The key material and the chain code are secret, so only the depth is displayed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedSecretKey")
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
+
+/// Derive the [`SecretKey`] of the provided key material.
+///
+/// Every secret is the blake2b-512 hash of its domain and the key, reduced modulo the JubJub order,
+/// so the derivation doesn't depend on the implementation of a random number generator
+pub(crate) fn derive_secret_key(key: &BlsScalar) -> SecretKey {
+    let a = hash_to_scalar(SECRET_A_DOMAIN, key);
+    let b = hash_to_scalar(SECRET_B_DOMAIN, key);
+
+    SecretKey::new(a, b)
+}
+
+fn hash_to_scalar(domain: u64, key: &BlsScalar) -> JubJubScalar {
+    let mut hasher = Blake2b::new();
+    hasher.update(domain.to_le_bytes());
+    hasher.update(key.to_bytes());
+
+    let mut bytes = [0x00u8; 64];
+    bytes.copy_from_slice(&hasher.finalize()[..]);
+
+    JubJubScalar::from_bytes_wide(&bytes)
+}
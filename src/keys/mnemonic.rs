@@ -0,0 +1,184 @@
+use crate::{Error, ExtendedSecretKey};
+
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::Hmac;
+use rand::RngCore;
+use sha2::{Digest, Sha256, Sha512};
+use unicode_normalization::UnicodeNormalization;
+
+/// Bits of the entropy and checksum encoded in every word
+const BITS_PER_WORD: usize = 11;
+/// Iterations of PBKDF2 to stretch the mnemonic into a seed
+const PBKDF2_ROUNDS: usize = 2048;
+/// Size of the seed created from the mnemonic
+pub const MNEMONIC_SEED_SIZE: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref WORDLIST: Vec<&'static str> =
+        include_str!("wordlist/english.txt").lines().collect();
+}
+
+/// BIP39 mnemonic phrase, represented by its entropy
+///
+/// The phrase is composed of 12, 15, 18, 21 or 24 words of the english word list, and the last
+/// word carries the checksum of the entropy.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+    /// Create a mnemonic from the provided entropy.
+    ///
+    /// The entropy must be 16, 20, 24, 28 or 32 bytes long
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, Error> {
+        match entropy.len() {
+            16 | 20 | 24 | 28 | 32 => Ok(Self {
+                entropy: entropy.to_vec(),
+            }),
+            len => Err(Error::InvalidLength {
+                field: "Mnemonic.entropy",
+                expected: ((len + 3) / 4 * 4).max(16).min(32),
+                actual: len,
+            }),
+        }
+    }
+
+    /// Create a new random mnemonic with the provided number of words from a random number
+    /// generator
+    pub fn from_rng<R: RngCore>(rng: &mut R, words: usize) -> Result<Self, Error> {
+        if words % 3 != 0 {
+            return Err(Error::InvalidLength {
+                field: "Mnemonic.words",
+                expected: ((words + 2) / 3 * 3).max(12).min(24),
+                actual: words,
+            });
+        }
+
+        let mut entropy = vec![0x00u8; words / 3 * 4];
+        rng.fill_bytes(entropy.as_mut_slice());
+
+        Self::from_entropy(entropy.as_slice())
+    }
+
+    /// Create a new random mnemonic with the provided number of words from [`rand::thread_rng`]
+    pub fn generate(words: usize) -> Result<Self, Error> {
+        Self::from_rng(&mut rand::thread_rng(), words)
+    }
+
+    /// Entropy encoded by the phrase
+    pub fn entropy(&self) -> &[u8] {
+        self.entropy.as_slice()
+    }
+
+    /// Space-separated words of the mnemonic
+    pub fn phrase(&self) -> String {
+        let checksum = checksum(self.entropy.as_slice());
+        let mut bytes = self.entropy.clone();
+        bytes.push(checksum);
+
+        let words = (self.entropy.len() * 8 + self.entropy.len() / 4) / BITS_PER_WORD;
+
+        (0..words)
+            .map(|w| {
+                let idx = (0..BITS_PER_WORD).fold(0usize, |idx, b| {
+                    let bit = w * BITS_PER_WORD + b;
+                    (idx << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as usize
+                });
+
+                WORDLIST[idx]
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Stretch the phrase into a seed with PBKDF2-HMAC-SHA512, salted with the passphrase
+    pub fn to_seed(&self, passphrase: &str) -> [u8; MNEMONIC_SEED_SIZE] {
+        let phrase: String = self.phrase().nfkd().collect();
+        let salt: String = format!("mnemonic{}", passphrase).nfkd().collect();
+
+        let mut seed = [0x00u8; MNEMONIC_SEED_SIZE];
+        pbkdf2::pbkdf2::<Hmac<Sha512>>(
+            phrase.as_bytes(),
+            salt.as_bytes(),
+            PBKDF2_ROUNDS,
+            &mut seed[..],
+        );
+
+        seed
+    }
+
+    /// Create the master of the hierarchical deterministic keys from the seed of the phrase
+    pub fn master_key(&self, passphrase: &str) -> ExtendedSecretKey {
+        // Infallible since the seed is always longer than the minimum
+        ExtendedSecretKey::from_seed(&self.to_seed(passphrase)[..]).unwrap()
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = Error;
+
+    /// Restore a mnemonic from its phrase, validating the words and the checksum
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s
+            .split_whitespace()
+            .map(|word| {
+                let word = word.to_lowercase();
+
+                WORDLIST
+                    .binary_search_by(|w| (*w).cmp(word.as_str()))
+                    .map_err(|_| Error::InvalidMnemonic)
+            })
+            .collect::<Result<Vec<usize>, Error>>()?;
+
+        if words.len() < 12 || words.len() > 24 || words.len() % 3 != 0 {
+            return Err(Error::InvalidMnemonic);
+        }
+
+        let bits = words.len() * BITS_PER_WORD;
+        let mut bytes = vec![0x00u8; (bits + 7) / 8];
+        for (w, idx) in words.iter().enumerate() {
+            for b in 0..BITS_PER_WORD {
+                if (idx >> (BITS_PER_WORD - 1 - b)) & 1 == 1 {
+                    let bit = w * BITS_PER_WORD + b;
+                    bytes[bit / 8] |= 1 << (7 - bit % 8);
+                }
+            }
+        }
+
+        let checksum_bits = bits / 33;
+        let entropy = &bytes[0..checksum_bits * 4];
+
+        let mask = 0xffu8 << (8 - checksum_bits);
+        if bytes[checksum_bits * 4] & mask != checksum(entropy) & mask {
+            return Err(Error::InvalidMnemonicChecksum);
+        }
+
+        Self::from_entropy(entropy)
+    }
+}
+
+impl fmt::Debug for Mnemonic {
+    /// The entropy is secret, so only the number of words is displayed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = (self.entropy.len() * 8 + self.entropy.len() / 4) / BITS_PER_WORD;
+
+        f.debug_struct("Mnemonic").field("words", &words).finish()
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.phrase())
+    }
+}
+
+/// The checksum is the leading bits of the SHA256 of the entropy; at most 8 bits are used
+fn checksum(entropy: &[u8]) -> u8 {
+    let mut hasher = Sha256::default();
+    hasher.input(entropy);
+
+    hasher.result()[0]
+}
@@ -1,7 +1,11 @@
+pub use derivation::{DerivationPath, ExtendedSecretKey, MIN_SEED_SIZE};
+pub use mnemonic::{Mnemonic, MNEMONIC_SEED_SIZE};
 pub use public::PublicKey;
 pub use secret::SecretKey;
 pub use view::ViewKey;
 
+mod derivation;
+mod mnemonic;
 mod public;
 mod secret;
 mod view;
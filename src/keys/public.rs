@@ -1,3 +1,4 @@
+use crate::error::ResultExt;
 use crate::{rpc, utils, Error, JubJubAffine, JubJubExtended, SecretKey};
 
 use std::convert::{TryFrom, TryInto};
@@ -51,13 +52,15 @@ impl TryFrom<rpc::PublicKey> for PublicKey {
     fn try_from(k: rpc::PublicKey) -> Result<Self, Self::Error> {
         let A = k
             .a_g
-            .ok_or(Error::InvalidPoint)
-            .and_then(|p| p.try_into())?;
+            .ok_or(Error::MissingField("PublicKey.a_g"))?
+            .try_into()
+            .context("PublicKey.a_g")?;
 
         let B = k
             .b_g
-            .ok_or(Error::InvalidPoint)
-            .and_then(|p| p.try_into())?;
+            .ok_or(Error::MissingField("PublicKey.b_g"))?
+            .try_into()
+            .context("PublicKey.b_g")?;
 
         Ok(Self::new(A, B))
     }
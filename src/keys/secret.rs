@@ -1,3 +1,4 @@
+use crate::error::ResultExt;
 use crate::{rpc, utils, Error, JubJubExtended, JubJubScalar, PublicKey, ViewKey};
 
 use jubjub::GENERATOR;
@@ -55,8 +56,14 @@ impl TryFrom<rpc::SecretKey> for SecretKey {
     type Error = Error;
 
     fn try_from(k: rpc::SecretKey) -> Result<Self, Self::Error> {
-        let a = k.a.ok_or(Error::InvalidPoint).and_then(|s| s.try_into())?;
-        let b = k.b.ok_or(Error::InvalidPoint).and_then(|s| s.try_into())?;
+        let a =
+            k.a.ok_or(Error::MissingField("SecretKey.a"))?
+                .try_into()
+                .context("SecretKey.a")?;
+        let b =
+            k.b.ok_or(Error::MissingField("SecretKey.b"))?
+                .try_into()
+                .context("SecretKey.b")?;
 
         Ok(Self::new(a, b))
     }
@@ -1,6 +1,11 @@
-use crate::{PublicKey, SecretKey, ViewKey};
+use super::derivation::derive_secret_key;
+use crate::{
+    BlsScalar, DerivationPath, Error, ExtendedSecretKey, Mnemonic, PublicKey, SecretKey, ViewKey,
+    MIN_SEED_SIZE,
+};
 
 use std::convert::TryFrom;
+use std::str::FromStr;
 
 #[test]
 fn sk_from_bytes() {
@@ -23,3 +28,230 @@ fn keys_encoding() {
     assert_eq!(vk, ViewKey::try_from(format!("{}", vk)).unwrap());
     assert_eq!(pk, PublicKey::try_from(format!("{}", pk)).unwrap());
 }
+
+#[test]
+fn hd_derivation() {
+    let seed = b"some seed of the master key".to_vec();
+
+    let master = ExtendedSecretKey::from_seed(seed.as_slice()).unwrap();
+    assert_eq!(
+        master,
+        ExtendedSecretKey::from_seed(seed.as_slice()).unwrap()
+    );
+    assert_ne!(
+        master,
+        ExtendedSecretKey::from_seed(b"other seed of the master key").unwrap()
+    );
+    assert_eq!(0, master.depth());
+
+    let account_a = master.account(0).unwrap();
+    let account_b = master.account(1).unwrap();
+
+    assert_eq!(1, account_a.depth());
+    assert_eq!(
+        account_a.secret_key(),
+        master.account(0).unwrap().secret_key()
+    );
+    assert_ne!(account_a.secret_key(), account_b.secret_key());
+    assert_ne!(account_a.view_key(), account_b.view_key());
+    assert_eq!(account_a.view_key(), account_a.secret_key().view_key());
+    assert_eq!(account_a.public_key(), account_a.secret_key().public_key());
+
+    let address = master.derive(&DerivationPath::address(0, 3)).unwrap();
+    assert_eq!(2, address.depth());
+    assert_eq!(address, account_a.child(3).unwrap());
+    assert_ne!(address.view_key(), account_a.view_key());
+}
+
+#[test]
+fn hd_seed_too_short() {
+    let seed = [0x01u8; MIN_SEED_SIZE];
+    assert!(ExtendedSecretKey::from_seed(&seed[..]).is_ok());
+
+    match ExtendedSecretKey::from_seed(&seed[1..]) {
+        Err(Error::InvalidLength {
+            field: "ExtendedSecretKey.seed",
+            expected: MIN_SEED_SIZE,
+            actual,
+        }) if actual == MIN_SEED_SIZE - 1 => (),
+        r => panic!("Unexpected master key result {:?}", r),
+    }
+
+    match ExtendedSecretKey::from_seed(&[]) {
+        Err(Error::InvalidLength { actual: 0, .. }) => (),
+        r => panic!("Unexpected master key result {:?}", r),
+    }
+}
+
+#[test]
+fn hd_debug_redacted() {
+    let mnemonic = Mnemonic::from_entropy(&[0x7fu8; 16]).unwrap();
+    let debug = format!("{:?}", mnemonic);
+    assert_eq!("Mnemonic { words: 12 }", debug);
+    assert!(!debug.contains("127"));
+    assert!(!debug.contains("legal"));
+
+    let master = mnemonic.master_key("");
+    let key = master.account(0).unwrap();
+    assert_eq!("ExtendedSecretKey { depth: 1 }", format!("{:?}", key));
+    assert_eq!("ExtendedSecretKey { depth: 0 }", format!("{:?}", master));
+}
+
+#[test]
+fn hd_secret_key_vectors() {
+    let vectors = [
+        (
+            BlsScalar::zero(),
+            "8b962edfe14569d55db05c1def3869dcac9f9f7d79ef5286dd863387bad50f0c",
+            "880f1449565d8b031264397d58b97af05c873e252e7d2e3edf832bbcf9d08b0d",
+        ),
+        (
+            BlsScalar::one(),
+            "2db84ebb6df01bc5aaada010a23669796d49cb1b2d3cd5db44dbe62dacbc3504",
+            "f0c27b14366c29535341a7b36cc3e2fb5aa94a3190516e917259849bb1034e06",
+        ),
+        (
+            -BlsScalar::one(),
+            "e5f20e3ce2c14878f941d0abf7cb788afdd5c289e9336c1e813e1696f042f202",
+            "7a10f1054355edec27e6a12eda6f75d8ebedb50ea68c30f2590a7c418d41820b",
+        ),
+    ];
+
+    for (key, a, b) in vectors.iter() {
+        let sk = derive_secret_key(key);
+
+        assert_eq!(*a, hex::encode(sk.a().to_bytes()));
+        assert_eq!(*b, hex::encode(sk.b().to_bytes()));
+    }
+}
+
+#[test]
+fn hd_derivation_path() {
+    let path = DerivationPath::from_str("m/0'/3").unwrap();
+
+    assert_eq!(&[0, 3], path.indexes());
+    assert_eq!(DerivationPath::address(0, 3), path);
+    assert_eq!("m/0'/3'", format!("{}", path));
+    assert_eq!(path, DerivationPath::try_from("m/0/3").unwrap());
+    assert_eq!(
+        DerivationPath::account(5).child(1),
+        DerivationPath::address(5, 1)
+    );
+    assert!(DerivationPath::from_str("m").unwrap().indexes().is_empty());
+
+    match DerivationPath::from_str("0/3") {
+        Err(Error::InvalidEncoding("DerivationPath")) => (),
+        r => panic!("Unexpected path {:?}", r),
+    }
+    match DerivationPath::from_str("m/a") {
+        Err(Error::InvalidEncoding("DerivationPath.indexes")) => (),
+        r => panic!("Unexpected path {:?}", r),
+    }
+    assert!(DerivationPath::from_str("m//1").is_err());
+}
+
+#[test]
+fn mnemonic_vectors() {
+    let mnemonic = Mnemonic::from_entropy(&[0x00u8; 16]).unwrap();
+    assert_eq!(
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+         about",
+        mnemonic.phrase()
+    );
+    assert_eq!(
+        "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+        hex::encode(&mnemonic.to_seed("TREZOR")[..])
+    );
+
+    // The master key and every derivation are a function of the seed, so the whole chain from
+    // the phrase to the key of a path is pinned by the seed vector
+    let seed = mnemonic.to_seed("TREZOR");
+    let master = mnemonic.master_key("TREZOR");
+    assert_eq!(master, ExtendedSecretKey::from_seed(&seed[..]).unwrap());
+
+    let path = DerivationPath::from_str("m/0'/3'").unwrap();
+    let key = master.derive(&path).unwrap();
+    assert_eq!(2, key.depth());
+    assert_eq!(key, master.child(0).unwrap().child(3).unwrap());
+    assert_eq!(key, master.account(0).unwrap().child(3).unwrap());
+    assert_eq!(key.secret_key().view_key(), key.view_key());
+    assert_ne!(key, mnemonic.master_key("").derive(&path).unwrap());
+
+    let mnemonic = Mnemonic::from_entropy(&[0x7fu8; 16]).unwrap();
+    assert_eq!(
+        "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        mnemonic.phrase()
+    );
+    assert_eq!(
+        "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+        hex::encode(&mnemonic.to_seed("TREZOR")[..])
+    );
+
+    let mnemonic = Mnemonic::from_entropy(&[0xffu8; 32]).unwrap();
+    assert_eq!(
+        "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo \
+         zoo vote",
+        mnemonic.phrase()
+    );
+}
+
+#[test]
+fn mnemonic_restore() {
+    for words in [12, 15, 18, 21, 24].iter() {
+        let mnemonic = Mnemonic::generate(*words).unwrap();
+        let phrase = format!("{}", mnemonic);
+
+        assert_eq!(*words, phrase.split(' ').count());
+        assert_eq!(mnemonic, Mnemonic::from_str(phrase.as_str()).unwrap());
+        assert_eq!(
+            mnemonic.master_key("").account(0).unwrap().secret_key(),
+            Mnemonic::from_str(phrase.as_str())
+                .unwrap()
+                .master_key("")
+                .account(0)
+                .unwrap()
+                .secret_key()
+        );
+    }
+
+    assert_ne!(
+        Mnemonic::generate(12).unwrap().master_key(""),
+        Mnemonic::generate(12).unwrap().master_key("")
+    );
+    match Mnemonic::generate(13) {
+        Err(Error::InvalidLength {
+            field: "Mnemonic.words",
+            expected: 15,
+            actual: 13,
+        }) => (),
+        r => panic!("Unexpected mnemonic {:?}", r),
+    }
+    match Mnemonic::from_entropy(&[0x00u8; 15]) {
+        Err(Error::InvalidLength {
+            field: "Mnemonic.entropy",
+            expected: 16,
+            actual: 15,
+        }) => (),
+        r => panic!("Unexpected mnemonic {:?}", r),
+    }
+}
+
+#[test]
+fn mnemonic_invalid() {
+    let checksum = ["abandon"; 12].join(" ");
+    match Mnemonic::from_str(checksum.as_str()) {
+        Err(Error::InvalidMnemonicChecksum) => (),
+        r => panic!("Unexpected restore result {:?}", r),
+    }
+
+    let unknown = format!("{} phoenix", ["abandon"; 11].join(" "));
+    match Mnemonic::from_str(unknown.as_str()) {
+        Err(Error::InvalidMnemonic) => (),
+        r => panic!("Unexpected restore result {:?}", r),
+    }
+
+    match Mnemonic::from_str("abandon about") {
+        Err(Error::InvalidMnemonic) => (),
+        r => panic!("Unexpected restore result {:?}", r),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::ResultExt;
 use crate::{rpc, utils, Error, JubJubAffine, JubJubExtended, JubJubScalar, PublicKey, SecretKey};
 
 use jubjub::GENERATOR;
@@ -63,12 +64,16 @@ impl TryFrom<rpc::ViewKey> for ViewKey {
     type Error = Error;
 
     fn try_from(k: rpc::ViewKey) -> Result<Self, Self::Error> {
-        let a = k.a.ok_or(Error::InvalidPoint).and_then(|s| s.try_into())?;
+        let a =
+            k.a.ok_or(Error::MissingField("ViewKey.a"))?
+                .try_into()
+                .context("ViewKey.a")?;
 
         let B = k
             .b_g
-            .ok_or(Error::InvalidPoint)
-            .and_then(|p| p.try_into())?;
+            .ok_or(Error::MissingField("ViewKey.b_g"))?
+            .try_into()
+            .context("ViewKey.b_g")?;
 
         Ok(Self::new(a, B))
     }
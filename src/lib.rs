@@ -6,17 +6,21 @@ pub use jubjub::Fr as JubJubScalar;
 pub use jubjub::{AffinePoint as JubJubAffine, ExtendedPoint as JubJubExtended};
 
 pub use crypto::MerkleProofProvider;
-pub use db::{NotesDb, NotesIter};
+pub use db::{NotesDb, NotesIter, NullifierProof, ValidationError};
 pub use error::Error;
-pub use keys::{PublicKey, SecretKey, ViewKey};
+pub use keys::{
+    DerivationPath, ExtendedSecretKey, Mnemonic, PublicKey, SecretKey, ViewKey, MIN_SEED_SIZE,
+    MNEMONIC_SEED_SIZE,
+};
 pub use note::{Note, NoteGenerator, NoteVariant, Nullifier, ObfuscatedNote, TransparentNote};
 pub use rpc::NoteType;
 pub use sodiumoxide::crypto::secretbox::xsalsa20poly1305::{Nonce, NONCEBYTES};
 pub use transaction::{
-    Transaction, TransactionInput, TransactionItem, TransactionOutput,
+    Transaction, TransactionBuilder, TransactionInput, TransactionItem, TransactionOutput,
     MAX_INPUT_NOTES_PER_TRANSACTION, MAX_NOTES_PER_TRANSACTION, MAX_OUTPUT_NOTES_PER_TRANSACTION,
-    TX_SERIALIZED_SIZE,
+    TX_SERIALIZED_SIZE, TX_VERSION,
 };
+pub use wallet::{mempool_balance, pending_balance, OwnedNote, ScannedNote, Scanner, Wallet};
 
 /// Crypto primitives
 pub mod crypto;
@@ -34,5 +38,7 @@ pub mod rpc;
 pub mod transaction;
 /// General toolkit
 pub mod utils;
+/// Key store, note scanning and balance tracking
+pub mod wallet;
 /// ZK Gadgets and value proof
 pub mod zk;
@@ -9,6 +9,7 @@ use std::fmt::Debug;
 use std::io;
 use std::ops::Mul;
 
+use blake2::{Blake2b, Digest};
 use jubjub::GENERATOR;
 
 /// Nullifier definition
@@ -26,7 +27,7 @@ mod tests;
 pub use nullifier::Nullifier;
 pub use obfuscated::ObfuscatedNote;
 pub use transparent::TransparentNote;
-pub use variant::NoteVariant;
+pub use variant::{NoteVariant, NOTE_SERIALIZED_SIZE};
 
 /// Trait for the notes construction
 pub trait NoteGenerator:
@@ -68,8 +69,8 @@ pub trait NoteGenerator:
         let vk = sk.view_key();
 
         let nullifier = self.generate_nullifier(&sk);
-        let value = self.value(Some(&vk));
-        let blinding_factor = self.blinding_factor(Some(&vk))?;
+        let value = self.try_value(&vk)?;
+        let blinding_factor = self.try_blinding_factor(&vk)?;
 
         let merkle_root = *merkle_opening.root();
 
@@ -120,6 +121,11 @@ pub trait NoteGenerator:
         (R, pk_r)
     }
 
+    /// Generate the view tag of the note from the DHKE `r · A`
+    fn generate_view_tag(r: &JubJubScalar, pk: &PublicKey) -> u8 {
+        crypto::view_tag(&pk.A().mul(r))
+    }
+
     /// Internally calls the [`crypto::encrypt`] to mask the value
     fn encrypt_value(r: &JubJubScalar, pk: &PublicKey, nonce: &Nonce, value: u64) -> [u8; 24] {
         let bytes = crypto::encrypt(r, pk, nonce, &value.to_le_bytes()[..]);
@@ -158,13 +164,13 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
     fn rpc_decrypted_note(&self, vk: &ViewKey) -> Result<rpc::DecryptedNote, Error> {
         let note_type = self.note().into();
         let pos = self.idx();
-        let value = self.value(Some(vk));
+        let value = self.try_value(vk)?;
         let nonce = Some((*self.nonce()).into());
         let r_g = Some((*self.R()).into());
         let pk_r = Some((*self.pk_r()).into());
         let value_commitment = Some((*self.value_commitment()).into());
 
-        let blinding_factor = self.blinding_factor(Some(vk))?;
+        let blinding_factor = self.try_blinding_factor(vk)?;
         let raw_blinding_factor = match self.note() {
             NoteType::Transparent => {
                 rpc::decrypted_note::RawBlindingFactor::TransparentBlindingFactor(
@@ -185,6 +191,7 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
             .map(|v| rpc::decrypted_note::RawValue::EncryptedValue(v.to_vec()))
             .unwrap_or(rpc::decrypted_note::RawValue::TransparentValue(value));
         let raw_value = Some(raw_value);
+        let view_tag = view_tag_to_rpc(self.view_tag());
 
         Ok(rpc::DecryptedNote {
             note_type,
@@ -197,6 +204,7 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
             blinding_factor,
             raw_blinding_factor,
             raw_value,
+            view_tag,
         })
     }
 
@@ -227,6 +235,9 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
     /// Attempt to decrypt the note value provided a [`ViewKey`]. Always succeeds for transparent
     /// notes, and will return random values for obfuscated notes provided the wrong view key.
     fn value(&self, vk: Option<&ViewKey>) -> u64;
+    /// Decrypt the note value provided a [`ViewKey`]. Always succeeds for transparent notes, and
+    /// fail with [`Error::DecryptionFailed`] for obfuscated notes provided the wrong view key.
+    fn try_value(&self, vk: &ViewKey) -> Result<u64, Error>;
 
     /// Return the raw encrypted bytes of the value. If the note is transparent, `None` is returned
     fn encrypted_value(&self) -> Option<&[u8; 24]>;
@@ -236,12 +247,18 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
     ///
     /// If the decrypt fails, a random value is returned
     fn blinding_factor(&self, vk: Option<&ViewKey>) -> Result<JubJubScalar, Error>;
+    /// Decrypt the blinding factor with the provided [`ViewKey`]
+    ///
+    /// Fail with [`Error::DecryptionFailed`] for obfuscated notes provided the wrong view key
+    fn try_blinding_factor(&self, vk: &ViewKey) -> Result<JubJubScalar, Error>;
     /// Return the raw encrypted value blinding factor
     fn encrypted_blinding_factor(&self) -> &[u8; 48];
     /// Return the `r · G` used for the DHKE randomness
     fn R(&self) -> &JubJubExtended;
     /// Return the public DHKE combined with the secret key of the owner of the note
     fn pk_r(&self) -> &JubJubExtended;
+    /// Return the short tag of the DHKE secret, if the note carries it
+    fn view_tag(&self) -> Option<u8>;
 
     /// Generate a `sk_r = H(a · R) + b`
     fn sk_r(&self, sk: &SecretKey) -> JubJubScalar {
@@ -255,8 +272,18 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
     /// view key
     ///
     /// This holds true if `H(a · R) + B == PKr`
+    ///
+    /// If the note carries a view tag, most of the notes not owned by the view key are rejected
+    /// before the hash and the scalar multiplication are performed
     fn is_owned_by(&self, vk: &ViewKey) -> bool {
         let aR = self.R().mul(vk.a());
+
+        if let Some(view_tag) = self.view_tag() {
+            if view_tag != crypto::view_tag(&aR) {
+                return false;
+            }
+        }
+
         let aR = crypto::hash_jubjub_projective_to_jubjub_scalar(&aR);
         let aR = JubJubExtended::from(GENERATOR).mul(&aR);
 
@@ -266,6 +293,83 @@ pub trait Note: Debug + Send + Sync + io::Read + io::Write {
     }
 }
 
+/// Return the second generator `H` of the value commitment `v · G + b · H`, derived from the
+/// hash of the base generator `G`
+pub fn value_commitment_generator() -> JubJubExtended {
+    let mut hasher = Blake2b::new();
+    hasher.update(GENERATOR.to_bytes());
+    let res = hasher.finalize();
+    let mut x = [0u64; 4];
+    let mut y = [0u64; 4];
+    x[0] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[0..8]).unwrap());
+    x[1] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[8..16]).unwrap());
+    x[2] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[16..24]).unwrap());
+    x[3] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[24..32]).unwrap());
+    y[0] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[32..40]).unwrap());
+    y[1] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[40..48]).unwrap());
+    y[2] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[48..56]).unwrap());
+    y[3] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[56..64]).unwrap());
+    let x_scalar = BlsScalar::from_raw(x);
+    let y_scalar = BlsScalar::from_raw(y);
+    let second_point = JubJubExtended::from(JubJubAffine::from_raw_unchecked(x_scalar, y_scalar));
+    let second_bytes = JubJubAffine::from(second_point).to_bytes();
+    JubJubExtended::from(JubJubAffine::from_bytes(second_bytes).unwrap())
+}
+
+/// Generate the value commitment `v · G + b · H` of a note
+pub fn generate_value_commitment(value: u64, blinding_factor: &JubJubScalar) -> JubJubExtended {
+    let value_commitment = (JubJubExtended::from(GENERATOR) * JubJubScalar::from(value))
+        + (value_commitment_generator() * blinding_factor);
+
+    JubJubExtended::from(JubJubAffine::from(value_commitment))
+}
+
+/// Serialized bytes size of an optional view tag
+pub(crate) const VIEW_TAG_SERIALIZED_SIZE: usize = 2;
+
+/// Serialize an optional view tag as a presence flag followed by the tag
+pub(crate) fn view_tag_to_bytes(view_tag: Option<u8>) -> [u8; VIEW_TAG_SERIALIZED_SIZE] {
+    match view_tag {
+        Some(t) => [0x01, t],
+        None => [0x00, 0x00],
+    }
+}
+
+/// Deserialize an optional view tag from a presence flag followed by the tag
+///
+/// A missing tag is encoded only as `[0x00, 0x00]`, so every tag has a single encoding
+pub(crate) fn view_tag_from_bytes(bytes: &[u8]) -> Result<Option<u8>, Error> {
+    match bytes {
+        [0x00, 0x00, ..] => Ok(None),
+        [0x01, t, ..] => Ok(Some(*t)),
+        [_, _, ..] => Err(Error::InvalidEncoding("view tag")),
+        _ => Err(Error::InvalidLength {
+            field: "view tag",
+            expected: VIEW_TAG_SERIALIZED_SIZE,
+            actual: bytes.len(),
+        }),
+    }
+}
+
+/// Deserialize an optional view tag from its RPC representation, where an empty set of bytes
+/// means no tag
+pub(crate) fn view_tag_from_rpc(bytes: &[u8]) -> Result<Option<u8>, Error> {
+    match bytes {
+        [] => Ok(None),
+        [t] => Ok(Some(*t)),
+        _ => Err(Error::InvalidLength {
+            field: "view tag",
+            expected: 1,
+            actual: bytes.len(),
+        }),
+    }
+}
+
+/// Represent an optional view tag for the RPC
+pub(crate) fn view_tag_to_rpc(view_tag: Option<u8>) -> Vec<u8> {
+    view_tag.map(|t| vec![t]).unwrap_or_default()
+}
+
 impl TryFrom<i32> for NoteType {
     type Error = Error;
 
@@ -273,7 +377,7 @@ impl TryFrom<i32> for NoteType {
         match note_type {
             0 => Ok(NoteType::Transparent),
             1 => Ok(NoteType::Obfuscated),
-            _ => Err(Error::InvalidParameters),
+            _ => Err(Error::InvalidEncoding("NoteType")),
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::error::ResultExt;
 use crate::{rpc, utils, BlsScalar, Error};
 
 use std::convert::{TryFrom, TryInto};
@@ -48,7 +49,10 @@ impl TryFrom<rpc::Nullifier> for Nullifier {
     type Error = Error;
 
     fn try_from(n: rpc::Nullifier) -> Result<Self, Error> {
-        let s = n.h.ok_or(Error::InvalidParameters)?.try_into()?;
+        let s =
+            n.h.ok_or(Error::MissingField("Nullifier.h"))?
+                .try_into()
+                .context("Nullifier.h")?;
 
         Ok(Nullifier::new(s))
     }
@@ -61,18 +65,17 @@ impl TryFrom<&rpc::Nullifier> for Nullifier {
         let s =
             n.h.as_ref()
                 .cloned()
-                .ok_or(Error::InvalidParameters)?
-                .try_into()?;
+                .ok_or(Error::MissingField("Nullifier.h"))?
+                .try_into()
+                .context("Nullifier.h")?;
 
         Ok(Nullifier::new(s))
     }
 }
 
 impl Read for Nullifier {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        buf.chunks_mut(utils::BLS_SCALAR_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
+    fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
+        utils::take_mut(&mut buf, utils::BLS_SCALAR_SERIALIZED_SIZE, "Nullifier")
             .and_then(|c| Ok(c.copy_from_slice(&self.s().to_bytes()[..])))
             .map_err::<io::Error, _>(|e| e.into())?;
         let n = utils::BLS_SCALAR_SERIALIZED_SIZE;
@@ -82,11 +85,8 @@ impl Read for Nullifier {
 }
 
 impl Write for Nullifier {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let nullifier = buf
-            .chunks(utils::BLS_SCALAR_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let nullifier = utils::take(&mut buf, utils::BLS_SCALAR_SERIALIZED_SIZE, "Nullifier")
             .and_then(utils::deserialize_bls_scalar)
             .map_err::<io::Error, _>(|e| e.into())?;
         let n = utils::BLS_SCALAR_SERIALIZED_SIZE;
@@ -1,5 +1,10 @@
+use super::{
+    view_tag_from_bytes, view_tag_from_rpc, view_tag_to_bytes, view_tag_to_rpc,
+    VIEW_TAG_SERIALIZED_SIZE,
+};
+use crate::error::ResultExt;
 use crate::{
-    crypto, rpc, utils, BlsScalar, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Note,
+    crypto, rpc, utils, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Note,
     NoteGenerator, NoteType, PublicKey, ViewKey, NONCEBYTES,
 };
 
@@ -8,8 +13,6 @@ use std::convert::{TryFrom, TryInto};
 use std::io::{self, Read, Write};
 use std::{cmp, fmt};
 
-use blake2::{Blake2b, Digest};
-use jubjub::GENERATOR;
 use kelvin::{ByteHash, Content, Sink, Source};
 use unprolix::Constructor;
 
@@ -28,6 +31,7 @@ pub struct ObfuscatedNote {
     idx: u64,
     pub encrypted_value: [u8; ENCRYPTED_VALUE_SIZE],
     pub encrypted_blinding_factor: [u8; ENCRYPTED_BLINDING_FACTOR_SIZE],
+    view_tag: Option<u8>,
 }
 
 impl fmt::Debug for ObfuscatedNote {
@@ -53,63 +57,72 @@ impl Read for ObfuscatedNote {
     fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
         let mut n = 0;
 
-        buf.chunks_mut(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| {
-                Ok(c.copy_from_slice(&JubJubAffine::from(self.value_commitment).to_bytes()[..]))
-            })
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "ObfuscatedNote.value_commitment",
+        )
+        .and_then(|c| {
+            Ok(c.copy_from_slice(&JubJubAffine::from(self.value_commitment).to_bytes()[..]))
+        })
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &mut buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        buf.chunks_mut(NONCEBYTES)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        utils::take_mut(&mut buf, NONCEBYTES, "ObfuscatedNote.nonce")
             .and_then(|mut c| Ok(c.write(&self.nonce.0)?))
             .map_err::<io::Error, _>(|e| e.into())?;
         n += NONCEBYTES;
-        buf = &mut buf[NONCEBYTES..];
 
-        buf.chunks_mut(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(self.R).to_bytes()[..])))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "ObfuscatedNote.R",
+        )
+        .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(self.R).to_bytes()[..])))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &mut buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        buf.chunks_mut(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(self.pk_r).to_bytes()[..])))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "ObfuscatedNote.pk_r",
+        )
+        .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(self.pk_r).to_bytes()[..])))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &mut buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        buf.chunks_mut(8)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        utils::take_mut(&mut buf, 8, "ObfuscatedNote.idx")
             .and_then(|mut c| Ok(c.write(&self.idx.to_le_bytes())?))
             .map_err::<io::Error, _>(|e| e.into())?;
         n += 8;
-        buf = &mut buf[8..];
 
-        buf.chunks_mut(ENCRYPTED_VALUE_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|mut c| Ok(c.write(&self.encrypted_value)?))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            ENCRYPTED_VALUE_SIZE,
+            "ObfuscatedNote.encrypted_value",
+        )
+        .and_then(|mut c| Ok(c.write(&self.encrypted_value)?))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += ENCRYPTED_VALUE_SIZE;
-        buf = &mut buf[ENCRYPTED_VALUE_SIZE..];
 
-        buf.chunks_mut(ENCRYPTED_BLINDING_FACTOR_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|mut c| Ok(c.write(&self.encrypted_blinding_factor)?))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            ENCRYPTED_BLINDING_FACTOR_SIZE,
+            "ObfuscatedNote.encrypted_blinding_factor",
+        )
+        .and_then(|mut c| Ok(c.write(&self.encrypted_blinding_factor)?))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += ENCRYPTED_BLINDING_FACTOR_SIZE;
 
+        utils::take_mut(
+            &mut buf,
+            VIEW_TAG_SERIALIZED_SIZE,
+            "ObfuscatedNote.view_tag",
+        )
+        .and_then(|mut c| Ok(c.write(&view_tag_to_bytes(self.view_tag))?))
+        .map_err::<io::Error, _>(|e| e.into())?;
+        n += VIEW_TAG_SERIALIZED_SIZE;
+
         Ok(n)
     }
 }
@@ -118,19 +131,16 @@ impl Write for ObfuscatedNote {
     fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
         let mut n = 0;
 
-        let value_commitment = buf
-            .chunks(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_compressed_jubjub)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let value_commitment = utils::take(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "ObfuscatedNote.value_commitment",
+        )
+        .and_then(utils::deserialize_compressed_jubjub)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        let nonce = buf
-            .chunks(NONCEBYTES)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        let nonce = utils::take(&mut buf, NONCEBYTES, "ObfuscatedNote.nonce")
             .and_then(|c| {
                 let mut n = [0x00u8; NONCEBYTES];
                 (&mut n[..]).write(c)?;
@@ -138,30 +148,26 @@ impl Write for ObfuscatedNote {
             })
             .map_err::<io::Error, _>(|e| e.into())?;
         n += NONCEBYTES;
-        buf = &buf[NONCEBYTES..];
 
-        let R = buf
-            .chunks(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_compressed_jubjub)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let R = utils::take(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "ObfuscatedNote.R",
+        )
+        .and_then(utils::deserialize_compressed_jubjub)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        let pk_r = buf
-            .chunks(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_compressed_jubjub)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let pk_r = utils::take(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "ObfuscatedNote.pk_r",
+        )
+        .and_then(utils::deserialize_compressed_jubjub)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        let idx = buf
-            .chunks(8)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        let idx = utils::take(&mut buf, 8, "ObfuscatedNote.idx")
             .and_then(|c| {
                 let mut i = [0x00u8; 8];
                 (&mut i[..]).write(c)?;
@@ -169,33 +175,42 @@ impl Write for ObfuscatedNote {
             })
             .map_err::<io::Error, _>(|e| e.into())?;
         n += 8;
-        buf = &buf[8..];
 
-        let encrypted_value = buf
-            .chunks(ENCRYPTED_VALUE_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| {
-                let mut v = [0x00u8; ENCRYPTED_VALUE_SIZE];
-                (&mut v[..]).write(c)?;
-                Ok(v)
-            })
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let encrypted_value = utils::take(
+            &mut buf,
+            ENCRYPTED_VALUE_SIZE,
+            "ObfuscatedNote.encrypted_value",
+        )
+        .and_then(|c| {
+            let mut v = [0x00u8; ENCRYPTED_VALUE_SIZE];
+            (&mut v[..]).write(c)?;
+            Ok(v)
+        })
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += ENCRYPTED_VALUE_SIZE;
-        buf = &buf[ENCRYPTED_VALUE_SIZE..];
 
-        let encrypted_blinding_factor = buf
-            .chunks(ENCRYPTED_BLINDING_FACTOR_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| {
-                let mut v = [0x00u8; ENCRYPTED_BLINDING_FACTOR_SIZE];
-                (&mut v[..]).write(c)?;
-                Ok(v)
-            })
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let encrypted_blinding_factor = utils::take(
+            &mut buf,
+            ENCRYPTED_BLINDING_FACTOR_SIZE,
+            "ObfuscatedNote.encrypted_blinding_factor",
+        )
+        .and_then(|c| {
+            let mut v = [0x00u8; ENCRYPTED_BLINDING_FACTOR_SIZE];
+            (&mut v[..]).write(c)?;
+            Ok(v)
+        })
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += ENCRYPTED_BLINDING_FACTOR_SIZE;
 
+        let view_tag = utils::take(
+            &mut buf,
+            VIEW_TAG_SERIALIZED_SIZE,
+            "ObfuscatedNote.view_tag",
+        )
+        .and_then(view_tag_from_bytes)
+        .map_err::<io::Error, _>(|e| e.into())?;
+        n += VIEW_TAG_SERIALIZED_SIZE;
+
         self.value_commitment = value_commitment;
         self.nonce = nonce;
         self.R = R;
@@ -203,6 +218,7 @@ impl Write for ObfuscatedNote {
         self.idx = idx;
         self.encrypted_value = encrypted_value;
         self.encrypted_blinding_factor = encrypted_blinding_factor;
+        self.view_tag = view_tag;
 
         Ok(n)
     }
@@ -221,32 +237,11 @@ impl NoteGenerator for ObfuscatedNote {
         blinding_factor: JubJubScalar,
     ) -> Self {
         let (R, pk_r) = Self::new_pk_r(r, pk);
-        let value_commitment = JubJubScalar::from(value);
-        let mut hasher = Blake2b::new();
-        hasher.update(GENERATOR.to_bytes());
-        let res = hasher.finalize();
-        let mut x = [0u64; 4];
-        let mut y = [0u64; 4];
-        x[0] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[0..8]).unwrap());
-        x[1] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[8..16]).unwrap());
-        x[2] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[16..24]).unwrap());
-        x[3] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[24..32]).unwrap());
-        y[0] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[32..40]).unwrap());
-        y[1] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[40..48]).unwrap());
-        y[2] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[48..56]).unwrap());
-        y[3] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[56..64]).unwrap());
-        let x_scalar = BlsScalar::from_raw(x);
-        let y_scalar = BlsScalar::from_raw(y);
-        let second_point =
-            JubJubExtended::from(JubJubAffine::from_raw_unchecked(x_scalar, y_scalar));
-        let second_bytes = JubJubAffine::from(second_point).to_bytes();
-        let second_point = JubJubExtended::from(JubJubAffine::from_bytes(second_bytes).unwrap());
-        let value_commitment =
-            (JubJubExtended::from(GENERATOR) * value_commitment) + (second_point * blinding_factor);
-        let value_commitment = JubJubExtended::from(JubJubAffine::from(value_commitment));
+        let value_commitment = super::generate_value_commitment(value, &blinding_factor);
 
         // Output notes have undefined idx
         let idx = 0;
+        let view_tag = Some(Self::generate_view_tag(r, pk));
 
         let encrypted_value = ObfuscatedNote::encrypt_value(&r, pk, &nonce, value);
         let encrypted_blinding_factor =
@@ -260,6 +255,7 @@ impl NoteGenerator for ObfuscatedNote {
             idx,
             encrypted_value,
             encrypted_blinding_factor,
+            view_tag,
         )
     }
 }
@@ -289,16 +285,23 @@ impl Note for ObfuscatedNote {
         &self.pk_r
     }
 
+    fn view_tag(&self) -> Option<u8> {
+        self.view_tag
+    }
+
     fn value(&self, vk: Option<&ViewKey>) -> u64 {
         let vk = vk.copied().unwrap_or_default();
 
-        let decrypt_value = crypto::decrypt(&self.R, &vk, &self.nonce, &self.encrypted_value[..]);
+        let decrypt_value =
+            crypto::decrypt_or_shuffle(&self.R, &vk, &self.nonce, &self.encrypted_value[..]);
 
-        let mut v = [0x00u8; 8];
-        let chunk = cmp::min(decrypt_value.len(), 8);
-        (&mut v[0..chunk]).copy_from_slice(&decrypt_value.as_slice()[0..chunk]);
+        value_from_bytes(decrypt_value.as_slice())
+    }
+
+    fn try_value(&self, vk: &ViewKey) -> Result<u64, Error> {
+        let decrypt_value = crypto::decrypt(&self.R, vk, &self.nonce, &self.encrypted_value[..])?;
 
-        u64::from_le_bytes(v)
+        Ok(value_from_bytes(decrypt_value.as_slice()))
     }
 
     fn encrypted_value(&self) -> Option<&[u8; ENCRYPTED_VALUE_SIZE]> {
@@ -312,7 +315,7 @@ impl Note for ObfuscatedNote {
     fn blinding_factor(&self, vk: Option<&ViewKey>) -> Result<JubJubScalar, Error> {
         let vk = vk.copied().unwrap_or_default();
 
-        let blinding_factor = crypto::decrypt(
+        let blinding_factor = crypto::decrypt_or_shuffle(
             &self.R,
             &vk,
             &self.nonce.increment_le(),
@@ -322,6 +325,17 @@ impl Note for ObfuscatedNote {
         utils::deserialize_jubjub_scalar(blinding_factor.as_slice()).map_err(|e| e.into())
     }
 
+    fn try_blinding_factor(&self, vk: &ViewKey) -> Result<JubJubScalar, Error> {
+        let blinding_factor = crypto::decrypt(
+            &self.R,
+            vk,
+            &self.nonce.increment_le(),
+            &self.encrypted_blinding_factor[..],
+        )?;
+
+        utils::deserialize_jubjub_scalar(blinding_factor.as_slice())
+    }
+
     fn encrypted_blinding_factor(&self) -> &[u8; ENCRYPTED_BLINDING_FACTOR_SIZE] {
         &self.encrypted_blinding_factor
     }
@@ -335,6 +349,7 @@ impl From<ObfuscatedNote> for rpc::Note {
         let r_g = Some(note.R.into());
         let pk_r = Some(note.pk_r.into());
         let value_commitment = Some(note.value_commitment.into());
+        let view_tag = view_tag_to_rpc(note.view_tag);
         let value = Some(rpc::note::Value::EncryptedValue(
             note.encrypted_value.to_vec(),
         ));
@@ -351,6 +366,7 @@ impl From<ObfuscatedNote> for rpc::Note {
             value_commitment,
             blinding_factor,
             value,
+            view_tag,
         }
     }
 }
@@ -360,31 +376,47 @@ impl TryFrom<rpc::Note> for ObfuscatedNote {
 
     fn try_from(note: rpc::Note) -> Result<Self, Self::Error> {
         if rpc::NoteType::try_from(note.note_type)? != NoteType::Obfuscated {
-            return Err(Error::InvalidParameters);
+            return Err(Error::InvalidEncoding("Note.note_type"));
         }
 
         let value_commitment = note
             .value_commitment
-            .ok_or(Error::InvalidParameters)?
-            .try_into()?;
-        let nonce = note.nonce.ok_or(Error::InvalidParameters)?.try_into()?;
-        let R = note.r_g.ok_or(Error::InvalidParameters)?.try_into()?;
-        let pk_r = note.pk_r.ok_or(Error::InvalidParameters)?.try_into()?;
+            .ok_or(Error::MissingField("Note.value_commitment"))?
+            .try_into()
+            .context("Note.value_commitment")?;
+        let nonce = note
+            .nonce
+            .ok_or(Error::MissingField("Note.nonce"))?
+            .try_into()
+            .context("Note.nonce")?;
+        let R = note
+            .r_g
+            .ok_or(Error::MissingField("Note.r_g"))?
+            .try_into()
+            .context("Note.r_g")?;
+        let pk_r = note
+            .pk_r
+            .ok_or(Error::MissingField("Note.pk_r"))?
+            .try_into()
+            .context("Note.pk_r")?;
         let idx = note.pos;
+        let view_tag = view_tag_from_rpc(note.view_tag.as_slice()).context("Note.view_tag")?;
 
-        let encrypted_value = match note.value.ok_or(Error::InvalidParameters)? {
-            rpc::note::Value::TransparentValue(_) => Err(Error::InvalidParameters),
+        let encrypted_value = match note.value.ok_or(Error::MissingField("Note.value"))? {
+            rpc::note::Value::TransparentValue(_) => Err(Error::InvalidEncoding("Note.value")),
             rpc::note::Value::EncryptedValue(v) => Ok(v),
         }?;
         let encrypted_value = utils::safe_24_chunk(encrypted_value.as_slice());
 
-        let encrypted_blinding_factor =
-            match note.blinding_factor.ok_or(Error::InvalidParameters)? {
-                rpc::note::BlindingFactor::TransparentBlindingFactor(_) => {
-                    Err(Error::InvalidParameters)
-                }
-                rpc::note::BlindingFactor::EncryptedBlindingFactor(b) => Ok(b),
-            }?;
+        let blinding_factor = note
+            .blinding_factor
+            .ok_or(Error::MissingField("Note.blinding_factor"))?;
+        let encrypted_blinding_factor = match blinding_factor {
+            rpc::note::BlindingFactor::TransparentBlindingFactor(_) => {
+                Err(Error::InvalidEncoding("Note.blinding_factor"))
+            }
+            rpc::note::BlindingFactor::EncryptedBlindingFactor(b) => Ok(b),
+        }?;
         let encrypted_blinding_factor = utils::safe_48_chunk(encrypted_blinding_factor.as_slice());
 
         Ok(ObfuscatedNote::new(
@@ -395,6 +427,7 @@ impl TryFrom<rpc::Note> for ObfuscatedNote {
             idx,
             encrypted_value,
             encrypted_blinding_factor,
+            view_tag,
         ))
     }
 }
@@ -405,26 +438,46 @@ impl TryFrom<rpc::DecryptedNote> for ObfuscatedNote {
     fn try_from(note: rpc::DecryptedNote) -> Result<Self, Self::Error> {
         let value_commitment = note
             .value_commitment
-            .ok_or(Error::InvalidParameters)?
-            .try_into()?;
-        let nonce = note.nonce.ok_or(Error::InvalidParameters)?.try_into()?;
-        let R = note.r_g.ok_or(Error::InvalidParameters)?.try_into()?;
-        let pk_r = note.pk_r.ok_or(Error::InvalidParameters)?.try_into()?;
+            .ok_or(Error::MissingField("DecryptedNote.value_commitment"))?
+            .try_into()
+            .context("DecryptedNote.value_commitment")?;
+        let nonce = note
+            .nonce
+            .ok_or(Error::MissingField("DecryptedNote.nonce"))?
+            .try_into()
+            .context("DecryptedNote.nonce")?;
+        let R = note
+            .r_g
+            .ok_or(Error::MissingField("DecryptedNote.r_g"))?
+            .try_into()
+            .context("DecryptedNote.r_g")?;
+        let pk_r = note
+            .pk_r
+            .ok_or(Error::MissingField("DecryptedNote.pk_r"))?
+            .try_into()
+            .context("DecryptedNote.pk_r")?;
         let idx = note.pos;
+        let view_tag =
+            view_tag_from_rpc(note.view_tag.as_slice()).context("DecryptedNote.view_tag")?;
 
-        let encrypted_value = match note.raw_value.ok_or(Error::InvalidParameters)? {
+        let raw_value = note
+            .raw_value
+            .ok_or(Error::MissingField("DecryptedNote.raw_value"))?;
+        let encrypted_value = match raw_value {
             rpc::decrypted_note::RawValue::EncryptedValue(v) => Ok(v),
-            _ => Err(Error::InvalidParameters),
+            _ => Err(Error::InvalidEncoding("DecryptedNote.raw_value")),
         }?;
         let encrypted_value = utils::safe_24_chunk(encrypted_value.as_slice());
 
-        let encrypted_blinding_factor =
-            match note.raw_blinding_factor.ok_or(Error::InvalidParameters)? {
-                rpc::decrypted_note::RawBlindingFactor::TransparentBlindingFactor(_) => {
-                    Err(Error::InvalidParameters)
-                }
-                rpc::decrypted_note::RawBlindingFactor::EncryptedBlindingFactor(b) => Ok(b),
-            }?;
+        let raw_blinding_factor = note
+            .raw_blinding_factor
+            .ok_or(Error::MissingField("DecryptedNote.raw_blinding_factor"))?;
+        let encrypted_blinding_factor = match raw_blinding_factor {
+            rpc::decrypted_note::RawBlindingFactor::TransparentBlindingFactor(_) => {
+                Err(Error::InvalidEncoding("DecryptedNote.raw_blinding_factor"))
+            }
+            rpc::decrypted_note::RawBlindingFactor::EncryptedBlindingFactor(b) => Ok(b),
+        }?;
         let encrypted_blinding_factor = utils::safe_48_chunk(encrypted_blinding_factor.as_slice());
 
         Ok(ObfuscatedNote::new(
@@ -435,6 +488,7 @@ impl TryFrom<rpc::DecryptedNote> for ObfuscatedNote {
             idx,
             encrypted_value,
             encrypted_blinding_factor,
+            view_tag,
         ))
     }
 }
@@ -450,6 +504,7 @@ impl<H: ByteHash> Content<H> for ObfuscatedNote {
 
         sink.write_all(&self.encrypted_value[..])?;
         sink.write_all(&self.encrypted_blinding_factor[..])?;
+        sink.write_all(&view_tag_to_bytes(self.view_tag))?;
 
         Ok(())
     }
@@ -468,6 +523,10 @@ impl<H: ByteHash> Content<H> for ObfuscatedNote {
         let mut encrypted_blinding_factor = [0x00u8; ENCRYPTED_BLINDING_FACTOR_SIZE];
         source.read_exact(&mut encrypted_blinding_factor)?;
 
+        let mut view_tag = [0x00u8; VIEW_TAG_SERIALIZED_SIZE];
+        source.read_exact(&mut view_tag)?;
+        let view_tag = view_tag_from_bytes(&view_tag).map_err::<io::Error, _>(|e| e.into())?;
+
         Ok(ObfuscatedNote::new(
             value_commitment,
             nonce,
@@ -476,6 +535,16 @@ impl<H: ByteHash> Content<H> for ObfuscatedNote {
             idx,
             encrypted_value,
             encrypted_blinding_factor,
+            view_tag,
         ))
     }
 }
+
+/// Reconstruct the value from its decrypted little-endian bytes
+fn value_from_bytes(bytes: &[u8]) -> u64 {
+    let mut v = [0x00u8; 8];
+    let chunk = cmp::min(bytes.len(), 8);
+    (&mut v[0..chunk]).copy_from_slice(&bytes[0..chunk]);
+
+    u64::from_le_bytes(v)
+}
@@ -1,6 +1,6 @@
 use crate::{
-    rpc, JubJubExtended, Note, NoteGenerator, NoteType, NoteVariant, ObfuscatedNote, PublicKey,
-    SecretKey, TransparentNote,
+    crypto, rpc, Error, JubJubExtended, Note, NoteGenerator, NoteType, NoteVariant, ObfuscatedNote,
+    PublicKey, SecretKey, TransparentNote,
 };
 
 use jubjub::GENERATOR;
@@ -135,3 +135,106 @@ fn content_implementations() {
     fuzz_content::<u64, Blake2b>();
     fuzz_content_iterations::<NoteVariant, Blake2b>(64);
 }
+
+#[test]
+fn note_bytes_invalid() {
+    let pk = SecretKey::default().public_key();
+
+    let notes: Vec<NoteVariant> = vec![
+        TransparentNote::output(&pk, 25).0.into(),
+        ObfuscatedNote::output(&pk, 25).0.into(),
+    ];
+
+    for mut note in notes {
+        let mut bytes = vec![0x00u8; 2048];
+        let n = note.read(bytes.as_mut_slice()).unwrap();
+
+        let e = NoteVariant::default().write(&bytes[..n - 1]).unwrap_err();
+        match e.into_inner().unwrap().downcast::<Error>().map(|e| *e) {
+            Ok(Error::InvalidLength {
+                expected: 2,
+                actual: 1,
+                ..
+            }) => (),
+            r => panic!("Unexpected error {:?}", r),
+        }
+
+        bytes[0] = 0x02;
+        let e = NoteVariant::default().write(&bytes[..n]).unwrap_err();
+        match e.into_inner().unwrap().downcast::<Error>().map(|e| *e) {
+            Ok(Error::InvalidEncoding("NoteVariant.note_type")) => (),
+            r => panic!("Unexpected error {:?}", r),
+        }
+    }
+}
+
+#[test]
+fn note_view_tag_encoding() {
+    assert_eq!(None, super::view_tag_from_bytes(&[0x00, 0x00]).unwrap());
+    assert_eq!(
+        Some(0x2a),
+        super::view_tag_from_bytes(&[0x01, 0x2a]).unwrap()
+    );
+    assert_eq!(
+        [0x00, 0x00],
+        super::view_tag_to_bytes(super::view_tag_from_bytes(&[0x00, 0x00]).unwrap())
+    );
+
+    for bytes in [[0x00, 0x01], [0x00, 0xff], [0x02, 0x00]].iter() {
+        match super::view_tag_from_bytes(&bytes[..]) {
+            Err(Error::InvalidEncoding("view tag")) => (),
+            r => panic!("Unexpected view tag {:?}", r),
+        }
+    }
+
+    match super::view_tag_from_bytes(&[0x00]) {
+        Err(Error::InvalidLength { actual: 1, .. }) => (),
+        r => panic!("Unexpected view tag {:?}", r),
+    }
+}
+
+#[test]
+fn note_view_tag() {
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+    let vk = sk.view_key();
+
+    let notes: Vec<NoteVariant> = vec![
+        TransparentNote::output(&pk, 25).0.into(),
+        ObfuscatedNote::output(&pk, 25).0.into(),
+    ];
+
+    for note in notes {
+        let view_tag = note.view_tag().unwrap();
+        assert_eq!(view_tag, crypto::view_tag(&note.R().mul(vk.a())));
+
+        let mut bytes = vec![0x00u8; 2048];
+        let mut variant = note;
+        variant.read(bytes.as_mut_slice()).unwrap();
+        let mut deser_note = NoteVariant::default();
+        deser_note.write(bytes.as_slice()).unwrap();
+        assert_eq!(Some(view_tag), deser_note.view_tag());
+
+        let rpc_note = rpc::Note::from(note);
+        assert_eq!(
+            Some(view_tag),
+            NoteVariant::try_from(rpc_note.clone()).unwrap().view_tag()
+        );
+
+        // Notes without a view tag fall back to the full ownership check
+        let mut untagged = rpc_note.clone();
+        untagged.view_tag.clear();
+        let untagged = NoteVariant::try_from(untagged).unwrap();
+        assert_eq!(None, untagged.view_tag());
+        assert!(untagged.is_owned_by(&vk));
+
+        let mut tampered = rpc_note.clone();
+        tampered.view_tag = vec![view_tag.wrapping_add(1)];
+        let tampered = NoteVariant::try_from(tampered).unwrap();
+        assert!(!tampered.is_owned_by(&vk));
+
+        let mut invalid = rpc_note;
+        invalid.view_tag = vec![view_tag, view_tag];
+        assert!(NoteVariant::try_from(invalid).is_err());
+    }
+}
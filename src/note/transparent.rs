@@ -1,13 +1,16 @@
+use super::{
+    view_tag_from_bytes, view_tag_from_rpc, view_tag_to_bytes, view_tag_to_rpc,
+    VIEW_TAG_SERIALIZED_SIZE,
+};
+use crate::error::ResultExt;
 use crate::{
-    rpc, utils, BlsScalar, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Note,
-    NoteGenerator, NoteType, PublicKey, ViewKey, NONCEBYTES,
+    rpc, utils, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Note, NoteGenerator,
+    NoteType, PublicKey, ViewKey, NONCEBYTES,
 };
 
 use std::convert::{TryFrom, TryInto};
 use std::io::{self, Read, Write};
 
-use blake2::{Blake2b, Digest};
-use jubjub::GENERATOR;
 use kelvin::{ByteHash, Content, Sink, Source};
 use unprolix::Constructor;
 
@@ -21,6 +24,7 @@ pub struct TransparentNote {
     idx: u64,
     pub value: u64,
     pub blinding_factor: JubJubScalar,
+    view_tag: Option<u8>,
 }
 
 impl PartialEq for TransparentNote {
@@ -40,63 +44,68 @@ impl Read for TransparentNote {
     fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
         let mut n = 0;
 
-        buf.chunks_mut(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| {
-                Ok(c.copy_from_slice(&JubJubAffine::from(&self.value_commitment).to_bytes()[..]))
-            })
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "TransparentNote.value_commitment",
+        )
+        .and_then(|c| {
+            Ok(c.copy_from_slice(&JubJubAffine::from(&self.value_commitment).to_bytes()[..]))
+        })
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &mut buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        buf.chunks_mut(NONCEBYTES)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        utils::take_mut(&mut buf, NONCEBYTES, "TransparentNote.nonce")
             .and_then(|mut c| Ok(c.write(&self.nonce.0)?))
             .map_err::<io::Error, _>(|e| e.into())?;
         n += NONCEBYTES;
-        buf = &mut buf[NONCEBYTES..];
 
-        buf.chunks_mut(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(&self.R).to_bytes()[..])))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "TransparentNote.R",
+        )
+        .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(&self.R).to_bytes()[..])))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &mut buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        buf.chunks_mut(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(&self.pk_r).to_bytes()[..])))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "TransparentNote.pk_r",
+        )
+        .and_then(|c| Ok(c.copy_from_slice(&JubJubAffine::from(&self.pk_r).to_bytes()[..])))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &mut buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        buf.chunks_mut(8)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        utils::take_mut(&mut buf, 8, "TransparentNote.idx")
             .and_then(|mut c| Ok(c.write(&self.idx.to_le_bytes())?))
             .map_err::<io::Error, _>(|e| e.into())?;
         n += 8;
-        buf = &mut buf[8..];
 
-        buf.chunks_mut(8)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        utils::take_mut(&mut buf, 8, "TransparentNote.value")
             .and_then(|mut c| Ok(c.write(&self.value.to_le_bytes())?))
             .map_err::<io::Error, _>(|e| e.into())?;
         n += 8;
-        buf = &mut buf[8..];
 
-        buf.chunks_mut(utils::BLS_SCALAR_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| Ok(c.copy_from_slice(&self.blinding_factor.to_bytes()[..])))
-            .map_err::<io::Error, _>(|e| e.into())?;
+        utils::take_mut(
+            &mut buf,
+            utils::BLS_SCALAR_SERIALIZED_SIZE,
+            "TransparentNote.blinding_factor",
+        )
+        .and_then(|c| Ok(c.copy_from_slice(&self.blinding_factor.to_bytes()[..])))
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::BLS_SCALAR_SERIALIZED_SIZE;
 
+        utils::take_mut(
+            &mut buf,
+            VIEW_TAG_SERIALIZED_SIZE,
+            "TransparentNote.view_tag",
+        )
+        .and_then(|mut c| Ok(c.write(&view_tag_to_bytes(self.view_tag))?))
+        .map_err::<io::Error, _>(|e| e.into())?;
+        n += VIEW_TAG_SERIALIZED_SIZE;
+
         Ok(n)
     }
 }
@@ -105,19 +114,16 @@ impl Write for TransparentNote {
     fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
         let mut n = 0;
 
-        let value_commitment = buf
-            .chunks(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_compressed_jubjub)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let value_commitment = utils::take(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "TransparentNote.value_commitment",
+        )
+        .and_then(utils::deserialize_compressed_jubjub)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        let nonce = buf
-            .chunks(NONCEBYTES)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        let nonce = utils::take(&mut buf, NONCEBYTES, "TransparentNote.nonce")
             .and_then(|c| {
                 let mut n = [0x00u8; NONCEBYTES];
                 (&mut n[..]).write(c)?;
@@ -125,30 +131,26 @@ impl Write for TransparentNote {
             })
             .map_err::<io::Error, _>(|e| e.into())?;
         n += NONCEBYTES;
-        buf = &buf[NONCEBYTES..];
 
-        let R = buf
-            .chunks(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_compressed_jubjub)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let R = utils::take(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "TransparentNote.R",
+        )
+        .and_then(utils::deserialize_compressed_jubjub)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        let pk_r = buf
-            .chunks(utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_compressed_jubjub)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let pk_r = utils::take(
+            &mut buf,
+            utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE,
+            "TransparentNote.pk_r",
+        )
+        .and_then(utils::deserialize_compressed_jubjub)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE;
-        buf = &buf[utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE..];
 
-        let idx = buf
-            .chunks(8)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        let idx = utils::take(&mut buf, 8, "TransparentNote.idx")
             .and_then(|c| {
                 let mut i = [0x00u8; 8];
                 (&mut i[..]).write(c)?;
@@ -156,12 +158,8 @@ impl Write for TransparentNote {
             })
             .map_err::<io::Error, _>(|e| e.into())?;
         n += 8;
-        buf = &buf[8..];
 
-        let value = buf
-            .chunks(8)
-            .next()
-            .ok_or(Error::InvalidParameters)
+        let value = utils::take(&mut buf, 8, "TransparentNote.value")
             .and_then(|c| {
                 let mut v = [0x00u8; 8];
                 (&mut v[..]).write(c)?;
@@ -169,16 +167,25 @@ impl Write for TransparentNote {
             })
             .map_err::<io::Error, _>(|e| e.into())?;
         n += 8;
-        buf = &buf[8..];
 
-        let blinding_factor = buf
-            .chunks(utils::JUBJUB_SCALAR_SERIALIZED_SIZE)
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_jubjub_scalar)
-            .map_err::<io::Error, _>(|e| e.into())?;
+        let blinding_factor = utils::take(
+            &mut buf,
+            utils::JUBJUB_SCALAR_SERIALIZED_SIZE,
+            "TransparentNote.blinding_factor",
+        )
+        .and_then(utils::deserialize_jubjub_scalar)
+        .map_err::<io::Error, _>(|e| e.into())?;
         n += utils::JUBJUB_SCALAR_SERIALIZED_SIZE;
 
+        let view_tag = utils::take(
+            &mut buf,
+            VIEW_TAG_SERIALIZED_SIZE,
+            "TransparentNote.view_tag",
+        )
+        .and_then(view_tag_from_bytes)
+        .map_err::<io::Error, _>(|e| e.into())?;
+        n += VIEW_TAG_SERIALIZED_SIZE;
+
         self.value_commitment = value_commitment;
         self.nonce = nonce;
         self.R = R;
@@ -186,6 +193,7 @@ impl Write for TransparentNote {
         self.idx = idx;
         self.value = value;
         self.blinding_factor = blinding_factor;
+        self.view_tag = view_tag;
 
         Ok(n)
     }
@@ -204,32 +212,11 @@ impl NoteGenerator for TransparentNote {
         blinding_factor: JubJubScalar,
     ) -> Self {
         let (R, pk_r) = Self::new_pk_r(r, pk);
-        let value_commitment = JubJubScalar::from(value);
-        let mut hasher = Blake2b::new();
-        hasher.update(GENERATOR.to_bytes());
-        let res = hasher.finalize();
-        let mut x = [0u64; 4];
-        let mut y = [0u64; 4];
-        x[0] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[0..8]).unwrap());
-        x[1] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[8..16]).unwrap());
-        x[2] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[16..24]).unwrap());
-        x[3] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[24..32]).unwrap());
-        y[0] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[32..40]).unwrap());
-        y[1] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[40..48]).unwrap());
-        y[2] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[48..56]).unwrap());
-        y[3] = u64::from_be_bytes(<[u8; 8]>::try_from(&res[56..64]).unwrap());
-        let x_scalar = BlsScalar::from_raw(x);
-        let y_scalar = BlsScalar::from_raw(y);
-        let second_point =
-            JubJubExtended::from(JubJubAffine::from_raw_unchecked(x_scalar, y_scalar));
-        let second_bytes = JubJubAffine::from(second_point).to_bytes();
-        let second_point = JubJubExtended::from(JubJubAffine::from_bytes(second_bytes).unwrap());
-        let value_commitment =
-            (JubJubExtended::from(GENERATOR) * value_commitment) + (second_point * blinding_factor);
-        let value_commitment = JubJubExtended::from(JubJubAffine::from(value_commitment));
+        let value_commitment = super::generate_value_commitment(value, &blinding_factor);
 
         // Output notes have undefined idx
         let idx = 0;
+        let view_tag = Some(Self::generate_view_tag(r, pk));
 
         TransparentNote::new(
             value_commitment,
@@ -239,6 +226,7 @@ impl NoteGenerator for TransparentNote {
             idx,
             value,
             blinding_factor,
+            view_tag,
         )
     }
 }
@@ -268,10 +256,18 @@ impl Note for TransparentNote {
         &self.pk_r
     }
 
+    fn view_tag(&self) -> Option<u8> {
+        self.view_tag
+    }
+
     fn value(&self, _vk: Option<&ViewKey>) -> u64 {
         self.value
     }
 
+    fn try_value(&self, _vk: &ViewKey) -> Result<u64, Error> {
+        Ok(self.value)
+    }
+
     fn encrypted_value(&self) -> Option<&[u8; 24]> {
         None
     }
@@ -284,6 +280,10 @@ impl Note for TransparentNote {
         Ok(self.blinding_factor)
     }
 
+    fn try_blinding_factor(&self, _vk: &ViewKey) -> Result<JubJubScalar, Error> {
+        Ok(self.blinding_factor)
+    }
+
     fn encrypted_blinding_factor(&self) -> &[u8; 48] {
         &[0x00u8; 48]
     }
@@ -297,6 +297,7 @@ impl From<TransparentNote> for rpc::Note {
         let r_g = Some(note.R.into());
         let pk_r = Some(note.pk_r.into());
         let value_commitment = Some(note.value_commitment.into());
+        let view_tag = view_tag_to_rpc(note.view_tag);
         let blinding_factor = Some(rpc::note::BlindingFactor::TransparentBlindingFactor(
             note.blinding_factor.into(),
         ));
@@ -311,6 +312,7 @@ impl From<TransparentNote> for rpc::Note {
             value_commitment,
             blinding_factor,
             value,
+            view_tag,
         }
     }
 }
@@ -320,27 +322,47 @@ impl TryFrom<rpc::Note> for TransparentNote {
 
     fn try_from(note: rpc::Note) -> Result<Self, Self::Error> {
         if rpc::NoteType::try_from(note.note_type)? != NoteType::Transparent {
-            return Err(Error::InvalidParameters);
+            return Err(Error::InvalidEncoding("Note.note_type"));
         }
 
         let value_commitment = note
             .value_commitment
-            .ok_or(Error::InvalidParameters)?
-            .try_into()?;
-        let nonce = note.nonce.ok_or(Error::InvalidParameters)?.try_into()?;
-        let R = note.r_g.ok_or(Error::InvalidParameters)?.try_into()?;
-        let pk_r = note.pk_r.ok_or(Error::InvalidParameters)?.try_into()?;
+            .ok_or(Error::MissingField("Note.value_commitment"))?
+            .try_into()
+            .context("Note.value_commitment")?;
+        let nonce = note
+            .nonce
+            .ok_or(Error::MissingField("Note.nonce"))?
+            .try_into()
+            .context("Note.nonce")?;
+        let R = note
+            .r_g
+            .ok_or(Error::MissingField("Note.r_g"))?
+            .try_into()
+            .context("Note.r_g")?;
+        let pk_r = note
+            .pk_r
+            .ok_or(Error::MissingField("Note.pk_r"))?
+            .try_into()
+            .context("Note.pk_r")?;
         let idx = note.pos;
+        let view_tag = view_tag_from_rpc(note.view_tag.as_slice()).context("Note.view_tag")?;
 
-        let blinding_factor = match note.blinding_factor.ok_or(Error::InvalidParameters)? {
+        let blinding_factor = note
+            .blinding_factor
+            .ok_or(Error::MissingField("Note.blinding_factor"))?;
+        let blinding_factor = match blinding_factor {
             rpc::note::BlindingFactor::TransparentBlindingFactor(b) => Ok(b),
-            rpc::note::BlindingFactor::EncryptedBlindingFactor(_) => Err(Error::InvalidParameters),
+            rpc::note::BlindingFactor::EncryptedBlindingFactor(_) => {
+                Err(Error::InvalidEncoding("Note.blinding_factor"))
+            }
         }?
-        .try_into()?;
+        .try_into()
+        .context("Note.blinding_factor")?;
 
-        let value = match note.value.ok_or(Error::InvalidParameters)? {
+        let value = match note.value.ok_or(Error::MissingField("Note.value"))? {
             rpc::note::Value::TransparentValue(v) => Ok(v),
-            rpc::note::Value::EncryptedValue(_) => Err(Error::InvalidParameters),
+            rpc::note::Value::EncryptedValue(_) => Err(Error::InvalidEncoding("Note.value")),
         }?;
 
         Ok(Self::new(
@@ -351,6 +373,7 @@ impl TryFrom<rpc::Note> for TransparentNote {
             idx,
             value,
             blinding_factor,
+            view_tag,
         ))
     }
 }
@@ -361,17 +384,33 @@ impl TryFrom<rpc::DecryptedNote> for TransparentNote {
     fn try_from(note: rpc::DecryptedNote) -> Result<Self, Self::Error> {
         let value_commitment = note
             .value_commitment
-            .ok_or(Error::InvalidParameters)?
-            .try_into()?;
-        let nonce = note.nonce.ok_or(Error::InvalidParameters)?.try_into()?;
-        let R = note.r_g.ok_or(Error::InvalidParameters)?.try_into()?;
-        let pk_r = note.pk_r.ok_or(Error::InvalidParameters)?.try_into()?;
+            .ok_or(Error::MissingField("DecryptedNote.value_commitment"))?
+            .try_into()
+            .context("DecryptedNote.value_commitment")?;
+        let nonce = note
+            .nonce
+            .ok_or(Error::MissingField("DecryptedNote.nonce"))?
+            .try_into()
+            .context("DecryptedNote.nonce")?;
+        let R = note
+            .r_g
+            .ok_or(Error::MissingField("DecryptedNote.r_g"))?
+            .try_into()
+            .context("DecryptedNote.r_g")?;
+        let pk_r = note
+            .pk_r
+            .ok_or(Error::MissingField("DecryptedNote.pk_r"))?
+            .try_into()
+            .context("DecryptedNote.pk_r")?;
         let idx = note.pos;
+        let view_tag =
+            view_tag_from_rpc(note.view_tag.as_slice()).context("DecryptedNote.view_tag")?;
         let value = note.value;
         let blinding_factor = note
             .blinding_factor
-            .ok_or(Error::InvalidParameters)?
-            .try_into()?;
+            .ok_or(Error::MissingField("DecryptedNote.blinding_factor"))?
+            .try_into()
+            .context("DecryptedNote.blinding_factor")?;
 
         Ok(Self::new(
             value_commitment,
@@ -381,6 +420,7 @@ impl TryFrom<rpc::DecryptedNote> for TransparentNote {
             idx,
             value,
             blinding_factor,
+            view_tag,
         ))
     }
 }
@@ -396,6 +436,7 @@ impl<H: ByteHash> Content<H> for TransparentNote {
         self.value.persist(sink)?;
 
         sink.write_all(&self.blinding_factor.to_bytes())?;
+        sink.write_all(&view_tag_to_bytes(self.view_tag))?;
         Ok(())
     }
 
@@ -410,6 +451,10 @@ impl<H: ByteHash> Content<H> for TransparentNote {
 
         let blinding_factor = utils::kelvin_source_to_jubjub_scalar(source)?;
 
+        let mut view_tag = [0x00u8; VIEW_TAG_SERIALIZED_SIZE];
+        source.read_exact(&mut view_tag)?;
+        let view_tag = view_tag_from_bytes(&view_tag).map_err::<io::Error, _>(|e| e.into())?;
+
         Ok(TransparentNote::new(
             value_commitment,
             nonce,
@@ -418,6 +463,7 @@ impl<H: ByteHash> Content<H> for TransparentNote {
             idx,
             value,
             blinding_factor,
+            view_tag,
         ))
     }
 }
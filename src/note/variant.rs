@@ -1,6 +1,9 @@
+use super::obfuscated::{ENCRYPTED_BLINDING_FACTOR_SIZE, ENCRYPTED_VALUE_SIZE};
+use super::VIEW_TAG_SERIALIZED_SIZE;
 use crate::{
-    crypto, rpc, BlsScalar, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Note,
+    crypto, rpc, utils, BlsScalar, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Note,
     NoteGenerator, NoteType, ObfuscatedNote, SecretKey, TransactionInput, TransparentNote, ViewKey,
+    NONCEBYTES,
 };
 
 use std::convert::{TryFrom, TryInto};
@@ -8,6 +11,24 @@ use std::io::{self, Read, Write};
 
 use kelvin::{ByteHash, Content, Sink, Source};
 
+/// Maximum serialized bytes size of a note variant, reached by the obfuscated notes
+pub const NOTE_SERIALIZED_SIZE: usize = 1
+    + 3 * utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE
+    + NONCEBYTES
+    + 8
+    + ENCRYPTED_VALUE_SIZE
+    + ENCRYPTED_BLINDING_FACTOR_SIZE
+    + VIEW_TAG_SERIALIZED_SIZE;
+
+/// Serialized bytes size of a transparent note variant
+const TRANSPARENT_NOTE_SERIALIZED_SIZE: usize = 1
+    + 3 * utils::COMPRESSED_JUBJUB_SERIALIZED_SIZE
+    + NONCEBYTES
+    + 8
+    + 8
+    + utils::JUBJUB_SCALAR_SERIALIZED_SIZE
+    + VIEW_TAG_SERIALIZED_SIZE;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NoteVariant {
     Transparent(TransparentNote),
@@ -29,10 +50,27 @@ impl NoteVariant {
     }
 }
 
+impl NoteVariant {
+    /// Serialized bytes size of the note variant identified by the leading byte of its
+    /// serialized form
+    pub fn serialized_size(tag: u8) -> Option<usize> {
+        match tag {
+            0x00 => Some(TRANSPARENT_NOTE_SERIALIZED_SIZE),
+            0x01 => Some(NOTE_SERIALIZED_SIZE),
+            _ => None,
+        }
+    }
+}
+
 impl Read for NoteVariant {
     fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
         if buf.is_empty() {
-            return Err(Error::InvalidParameters.into());
+            return Err(Error::InvalidLength {
+                field: "NoteVariant",
+                expected: 1,
+                actual: 0,
+            }
+            .into());
         }
 
         let mut n = 0;
@@ -56,7 +94,12 @@ impl Read for NoteVariant {
 impl Write for NoteVariant {
     fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
         if buf.is_empty() {
-            return Err(Error::InvalidParameters.into());
+            return Err(Error::InvalidLength {
+                field: "NoteVariant",
+                expected: 1,
+                actual: 0,
+            }
+            .into());
         }
 
         let mut n = 0;
@@ -76,7 +119,7 @@ impl Write for NoteVariant {
                 n += note.write(buf)?;
                 *self = NoteVariant::Obfuscated(note);
             }
-            _ => return Err(Error::InvalidParameters.into()),
+            _ => return Err(Error::InvalidEncoding("NoteVariant.note_type").into()),
         };
 
         Ok(n)
@@ -193,6 +236,13 @@ impl Note for NoteVariant {
         }
     }
 
+    fn view_tag(&self) -> Option<u8> {
+        match self {
+            NoteVariant::Transparent(note) => note.view_tag(),
+            NoteVariant::Obfuscated(note) => note.view_tag(),
+        }
+    }
+
     fn value(&self, vk: Option<&ViewKey>) -> u64 {
         match self {
             NoteVariant::Transparent(note) => note.value(vk),
@@ -200,6 +250,13 @@ impl Note for NoteVariant {
         }
     }
 
+    fn try_value(&self, vk: &ViewKey) -> Result<u64, Error> {
+        match self {
+            NoteVariant::Transparent(note) => note.try_value(vk),
+            NoteVariant::Obfuscated(note) => note.try_value(vk),
+        }
+    }
+
     fn encrypted_value(&self) -> Option<&[u8; 24]> {
         match self {
             NoteVariant::Transparent(note) => note.encrypted_value(),
@@ -221,6 +278,13 @@ impl Note for NoteVariant {
         }
     }
 
+    fn try_blinding_factor(&self, vk: &ViewKey) -> Result<JubJubScalar, Error> {
+        match self {
+            NoteVariant::Transparent(note) => note.try_blinding_factor(vk),
+            NoteVariant::Obfuscated(note) => note.try_blinding_factor(vk),
+        }
+    }
+
     fn encrypted_blinding_factor(&self) -> &[u8; 48] {
         match self {
             NoteVariant::Transparent(note) => note.encrypted_blinding_factor(),
@@ -0,0 +1,40 @@
+use crate::error::ResultExt;
+use crate::{crypto, rpc, BlsScalar, Error, Transaction};
+
+use std::convert::TryFrom;
+
+use crate::rpc::hash_request::Request;
+
+/// Calculate the poseidon hash of a [`rpc::HashRequest`].
+///
+/// The binary inputs are mapped to scalars via [`crypto::bytes_to_scalars`], and hashed via
+/// [`crypto::hash_bytes`]. The transactions are hashed via the sponge hash of their
+/// [`Transaction::hash`], in order, regardless of their number.
+pub fn hash_request(request: rpc::HashRequest) -> Result<BlsScalar, Error> {
+    match request
+        .request
+        .ok_or(Error::MissingField("HashRequest.request"))?
+    {
+        Request::BinInputs(r) => {
+            if r.inputs.is_empty() {
+                return Err(Error::MissingField("BinaryHashRequest.inputs"));
+            }
+
+            crypto::hash_bytes(r.inputs.as_slice())
+        }
+        Request::TxInputs(r) => {
+            if r.txs.is_empty() {
+                return Err(Error::MissingField("TransactionHashRequest.txs"));
+            }
+
+            let hashes = r
+                .txs
+                .into_iter()
+                .map(|tx| Transaction::try_from(tx).map(|tx| tx.hash()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("TransactionHashRequest.txs")?;
+
+            Ok(crypto::sponge_hash(hashes.as_slice()))
+        }
+    }
+}
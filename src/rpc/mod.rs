@@ -1,6 +1,14 @@
+/// Poseidon hash of the `Crypto` service requests
+pub mod hash;
+/// Tonic server of the phoenix wallet services
+pub mod server;
+/// Mapping of the phoenix errors to gRPC status codes and details
+pub mod status;
 /// General type conversion from/to rpc types
 pub mod types;
 
+pub use server::{serve, CryptoService, RuskService};
+
 tonic::include_proto!("rusk");
 
 #[cfg(test)]
@@ -0,0 +1,413 @@
+use crate::error::ResultExt;
+use crate::{
+    db, rpc, utils, wallet, Error, Note, NotesDb, PublicKey, ScannedNote, Scanner, SecretKey,
+    Transaction, TransactionBuilder, ViewKey,
+};
+
+use std::convert::{TryFrom, TryInto};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::rpc::contract_call_tx::ContractCall;
+use crate::rpc::crypto_server::{Crypto, CryptoServer};
+use crate::rpc::rusk_server::{Rusk, RuskServer};
+
+/// Implementation of the phoenix wallet services of [`rpc::rusk_server::Rusk`] on top of a
+/// [`NotesDb`].
+///
+/// The consensus, stake and bid services are not served by phoenix, and reply with
+/// [`tonic::Code::Unimplemented`].
+#[derive(Clone)]
+pub struct RuskService {
+    db: Arc<RwLock<NotesDb>>,
+}
+
+impl RuskService {
+    /// Create a new service that serves the provided notes db
+    pub fn new(db: NotesDb) -> Self {
+        Self {
+            db: Arc::new(RwLock::new(db)),
+        }
+    }
+
+    /// Shared notes db of the service
+    pub fn db(&self) -> Arc<RwLock<NotesDb>> {
+        Arc::clone(&self.db)
+    }
+
+    fn read_db(db: &RwLock<NotesDb>) -> Result<RwLockReadGuard<'_, NotesDb>, Status> {
+        db.read()
+            .map_err(|_| Status::internal("The notes db lock is poisoned"))
+    }
+
+    /// Run the closure with the notes db on the blocking threads of the runtime, so the scans and
+    /// proofs don't stall the executor
+    async fn with_db<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&NotesDb) -> Result<T, Status> + Send + 'static,
+    {
+        let db = self.db();
+
+        blocking(move || f(&*Self::read_db(&db)?)).await
+    }
+
+    /// Scan the db for the notes owned by the view key
+    fn owned_notes(db: &NotesDb, vk: ViewKey) -> Result<Vec<ScannedNote>, Error> {
+        Scanner::new(vec![vk]).scan(db, 0..db.notes_count(), |_, _| ())
+    }
+}
+
+/// Implementation of [`rpc::crypto_server::Crypto`], the poseidon hashing service served along
+/// with the [`RuskService`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CryptoService;
+
+/// Serve the [`RuskService`] of the provided notes db, and the [`CryptoService`], on the provided
+/// address, until the server fails
+pub async fn serve(addr: SocketAddr, db: NotesDb) -> Result<(), Error> {
+    Server::builder()
+        .add_service(RuskServer::new(RuskService::new(db)))
+        .add_service(CryptoServer::new(CryptoService))
+        .serve(addr)
+        .await
+        .map_err(Error::other)
+}
+
+/// Run the closure on the blocking threads of the runtime
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Status> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|_| Status::internal("The blocking task failed"))?
+}
+
+fn unimplemented<T>() -> Result<Response<T>, Status> {
+    Err(Status::unimplemented("Not served by phoenix"))
+}
+
+/// Phoenix transaction carried by the contract call, if any
+fn contract_call_transaction(call: ContractCall) -> Option<rpc::Transaction> {
+    match call {
+        ContractCall::Tx(tx) => Some(tx),
+        ContractCall::Withdraw(c) => c.tx,
+        ContractCall::Stake(c) => c.tx,
+        ContractCall::Bid(c) => c.tx,
+        ContractCall::Slash(c) => c.tx,
+        ContractCall::Distribute(c) => c.tx,
+        ContractCall::WithdrawStake(c) => c.tx,
+        ContractCall::WithdrawBid(c) => c.tx,
+    }
+}
+
+#[tonic::async_trait]
+impl Rusk for RuskService {
+    async fn echo(
+        &self,
+        _request: Request<rpc::EchoRequest>,
+    ) -> Result<Response<rpc::EchoResponse>, Status> {
+        Ok(Response::new(rpc::EchoResponse {}))
+    }
+
+    async fn validate_state_transition(
+        &self,
+        _request: Request<rpc::ValidateStateTransitionRequest>,
+    ) -> Result<Response<rpc::ValidateStateTransitionResponse>, Status> {
+        unimplemented()
+    }
+
+    async fn execute_state_transition(
+        &self,
+        _request: Request<rpc::ExecuteStateTransitionRequest>,
+    ) -> Result<Response<rpc::ExecuteStateTransitionResponse>, Status> {
+        unimplemented()
+    }
+
+    async fn generate_score(
+        &self,
+        _request: Request<rpc::GenerateScoreRequest>,
+    ) -> Result<Response<rpc::GenerateScoreResponse>, Status> {
+        unimplemented()
+    }
+
+    async fn verify_score(
+        &self,
+        _request: Request<rpc::VerifyScoreRequest>,
+    ) -> Result<Response<rpc::VerifyScoreResponse>, Status> {
+        unimplemented()
+    }
+
+    /// Create a secret key from the provided seed bytes. If no bytes are provided, the key is
+    /// random
+    async fn generate_secret_key(
+        &self,
+        request: Request<rpc::GenerateSecretKeyRequest>,
+    ) -> Result<Response<rpc::GenerateSecretKeyResponse>, Status> {
+        let seed = request.into_inner().b;
+        let sk = if seed.is_empty() {
+            SecretKey::default()
+        } else {
+            SecretKey::from(seed.as_slice())
+        };
+
+        Ok(Response::new(rpc::GenerateSecretKeyResponse {
+            sk: Some(sk.into()),
+            vk: Some(sk.view_key().into()),
+            pk: Some(sk.public_key().into()),
+        }))
+    }
+
+    async fn keys(
+        &self,
+        request: Request<rpc::SecretKey>,
+    ) -> Result<Response<rpc::KeysResponse>, Status> {
+        let sk = SecretKey::try_from(request.into_inner())?;
+
+        Ok(Response::new(rpc::KeysResponse {
+            vk: Some(sk.view_key().into()),
+            pk: Some(sk.public_key().into()),
+        }))
+    }
+
+    async fn full_scan_owned_notes(
+        &self,
+        request: Request<rpc::ViewKey>,
+    ) -> Result<Response<rpc::OwnedNotesResponse>, Status> {
+        let vk = ViewKey::try_from(request.into_inner())?;
+
+        let notes = self
+            .with_db(move |db| {
+                Self::owned_notes(db, vk)?
+                    .iter()
+                    .map(|n| n.note().rpc_decrypted_note(&vk))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(Status::from)
+            })
+            .await?;
+
+        Ok(Response::new(rpc::OwnedNotesResponse { notes }))
+    }
+
+    /// Create and prove a transaction that spends the unspent notes of the sender. The remainder
+    /// is sent back to the sender as an obfuscated note
+    async fn new_transaction(
+        &self,
+        request: Request<rpc::NewTransactionRequest>,
+    ) -> Result<Response<rpc::Transaction>, Status> {
+        let request = request.into_inner();
+
+        let sk: SecretKey = request
+            .sk
+            .ok_or(Error::MissingField("NewTransactionRequest.sk"))?
+            .try_into()
+            .context("NewTransactionRequest.sk")?;
+        let recipient: PublicKey = request
+            .recipient
+            .ok_or(Error::MissingField("NewTransactionRequest.recipient"))?
+            .try_into()
+            .context("NewTransactionRequest.recipient")?;
+        let (fee, obfuscated, value) = (request.fee, request.obfuscated, request.value);
+
+        // The lock is released once the transaction is built, before it is proven
+        let mut tx = self
+            .with_db(move |db| {
+                let notes: Vec<_> = Self::owned_notes(db, sk.view_key())?
+                    .iter()
+                    .map(|n| *n.note())
+                    .collect();
+                let nullifiers: Vec<_> = notes.iter().map(|n| n.generate_nullifier(&sk)).collect();
+                let spent = db.fetch_nullifiers(nullifiers.as_slice())?;
+
+                let unspent = notes
+                    .into_iter()
+                    .zip(spent.into_iter())
+                    .filter(|(_, spent)| spent.is_none())
+                    .map(|(note, _)| note);
+
+                let builder = TransactionBuilder::new(sk).notes(unspent).fee(fee);
+                let builder = if obfuscated {
+                    builder.output(recipient, value)
+                } else {
+                    builder.transparent_output(recipient, value)
+                };
+
+                Ok(builder.build(db)?)
+            })
+            .await?;
+
+        let tx = blocking(move || {
+            tx.prove()?;
+            tx.clear_sensitive_info();
+
+            Ok(tx)
+        })
+        .await?;
+
+        let mut tx = rpc::Transaction::try_from(tx)?;
+        tx.data = request.data;
+
+        Ok(Response::new(tx))
+    }
+
+    /// Total received by the view key: the sum of the values of all the notes it owns.
+    ///
+    /// The nullifiers can't be generated without the secret key, so the spent notes are included,
+    /// as documented on `rusk.proto`. This is not the spendable balance
+    async fn get_balance(
+        &self,
+        request: Request<rpc::GetBalanceRequest>,
+    ) -> Result<Response<rpc::GetBalanceResponse>, Status> {
+        let vk: ViewKey = request
+            .into_inner()
+            .vk
+            .ok_or(Error::MissingField("GetBalanceRequest.vk"))?
+            .try_into()
+            .context("GetBalanceRequest.vk")?;
+
+        let balance = self
+            .with_db(move |db| {
+                let notes = Self::owned_notes(db, vk)?;
+
+                Ok(utils::checked_sum(notes.iter().map(|n| *n.value()))?)
+            })
+            .await?;
+
+        Ok(Response::new(rpc::GetBalanceResponse { balance }))
+    }
+
+    /// Validate the phoenix transaction against the current state of the notes db
+    async fn verify_transaction(
+        &self,
+        request: Request<rpc::ContractCallTx>,
+    ) -> Result<Response<rpc::VerifyTransactionResponse>, Status> {
+        let tx = match request.into_inner().contract_call {
+            Some(ContractCall::Tx(tx)) => Transaction::try_from(tx).context("ContractCallTx.tx")?,
+            _ => return Err(Error::MissingField("ContractCallTx.tx").into()),
+        };
+
+        let verified = self
+            .with_db(move |db| match db.validate_transaction(&tx) {
+                Ok(()) => Ok(true),
+                Err(db::ValidationError::Storage(e)) => Err(e.into()),
+                Err(_) => Ok(false),
+            })
+            .await?;
+
+        Ok(Response::new(rpc::VerifyTransactionResponse { verified }))
+    }
+
+    /// Balance of the view key considering the pending transactions. The contract calls are
+    /// accounted via the phoenix transaction they carry.
+    ///
+    /// The request only carries the view key, so the nullifiers of the owned confirmed notes
+    /// can't be generated, and neither their stored nor their pending spends can be detected. If
+    /// the view key owns any confirmed note, the request fails with [`Error::SecretKeyRequired`]
+    /// as soon as the first one is found, and [`wallet::mempool_balance`] is to be used with the
+    /// secret key. Otherwise, the balance is the sum of the owned pending outputs, as in
+    /// [`wallet::pending_balance`]. This contract is documented on `rusk.proto`
+    async fn calculate_mempool_balance(
+        &self,
+        request: Request<rpc::CalculateMempoolBalanceRequest>,
+    ) -> Result<Response<rpc::GetBalanceResponse>, Status> {
+        let request = request.into_inner();
+
+        let vk: ViewKey = request
+            .vk
+            .ok_or(Error::MissingField("CalculateMempoolBalanceRequest.vk"))?
+            .try_into()
+            .context("CalculateMempoolBalanceRequest.vk")?;
+
+        let pending = request
+            .txs
+            .into_iter()
+            .map(|c| {
+                c.contract_call
+                    .ok_or(Error::MissingField("ContractCallTx.contract_call"))
+            })
+            .filter_map(|c| c.map(contract_call_transaction).transpose())
+            .map(|tx| tx.and_then(Transaction::try_from))
+            .collect::<Result<Vec<_>, _>>()
+            .context("CalculateMempoolBalanceRequest.txs")?;
+
+        let balance = self
+            .with_db(move |db| {
+                if Scanner::new(vec![vk]).owns_any(db, 0..db.notes_count())? {
+                    return Err(Error::SecretKeyRequired.into());
+                }
+
+                Ok(wallet::pending_balance(&vk, pending.as_slice())?)
+            })
+            .await?;
+
+        Ok(Response::new(rpc::GetBalanceResponse { balance }))
+    }
+
+    async fn new_stake(
+        &self,
+        _request: Request<rpc::StakeTransactionRequest>,
+    ) -> Result<Response<rpc::StakeTransaction>, Status> {
+        unimplemented()
+    }
+
+    async fn verify_stake(
+        &self,
+        _request: Request<rpc::StakeTransaction>,
+    ) -> Result<Response<rpc::VerifyTransactionResponse>, Status> {
+        unimplemented()
+    }
+
+    async fn new_withdraw_stake(
+        &self,
+        _request: Request<rpc::WithdrawStakeTransactionRequest>,
+    ) -> Result<Response<rpc::WithdrawStakeTransaction>, Status> {
+        unimplemented()
+    }
+
+    async fn new_bid(
+        &self,
+        _request: Request<rpc::BidTransactionRequest>,
+    ) -> Result<Response<rpc::BidTransaction>, Status> {
+        unimplemented()
+    }
+
+    async fn new_withdraw_bid(
+        &self,
+        _request: Request<rpc::WithdrawBidTransactionRequest>,
+    ) -> Result<Response<rpc::WithdrawBidTransaction>, Status> {
+        unimplemented()
+    }
+
+    async fn new_withdraw_fees(
+        &self,
+        _request: Request<rpc::WithdrawFeesTransactionRequest>,
+    ) -> Result<Response<rpc::WithdrawFeesTransaction>, Status> {
+        unimplemented()
+    }
+
+    async fn new_slash(
+        &self,
+        _request: Request<rpc::SlashTransactionRequest>,
+    ) -> Result<Response<rpc::SlashTransaction>, Status> {
+        unimplemented()
+    }
+}
+
+#[tonic::async_trait]
+impl Crypto for CryptoService {
+    async fn hash(
+        &self,
+        request: Request<rpc::HashRequest>,
+    ) -> Result<Response<rpc::HashResponse>, Status> {
+        let hash = rpc::hash::hash_request(request.into_inner())?;
+
+        Ok(Response::new(rpc::HashResponse {
+            hash: Some(hash.into()),
+        }))
+    }
+}
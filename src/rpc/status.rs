@@ -0,0 +1,197 @@
+use crate::Error;
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use prost::Message;
+use tonic::{Code, Status};
+
+/// Domain of the [`ErrorInfo`] reasons reported by phoenix
+pub const ERROR_DOMAIN: &str = "phoenix.dusk.network";
+
+/// Type url of the [`ErrorInfo`] packed in the status details
+pub const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+/// `google.rpc.Status`, the message encoded in the details of a [`Status`] that the gRPC
+/// implementations decode along with the status code
+#[derive(Clone, PartialEq, Message)]
+pub struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: String,
+    #[prost(message, repeated, tag = "3")]
+    pub details: Vec<Any>,
+}
+
+/// `google.protobuf.Any`
+#[derive(Clone, PartialEq, Message)]
+pub struct Any {
+    #[prost(string, tag = "1")]
+    pub type_url: String,
+    #[prost(bytes, tag = "2")]
+    pub value: Vec<u8>,
+}
+
+/// `google.rpc.ErrorInfo`, the reason of the error as a constant of the [`ERROR_DOMAIN`] and
+/// its context
+#[derive(Clone, PartialEq, Message)]
+pub struct ErrorInfo {
+    #[prost(string, tag = "1")]
+    pub reason: String,
+    #[prost(string, tag = "2")]
+    pub domain: String,
+    #[prost(map = "string, string", tag = "3")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl Error {
+    /// gRPC status code of the error. The client faults are reported with
+    /// [`Code::InvalidArgument`], [`Code::NotFound`], [`Code::AlreadyExists`] or
+    /// [`Code::FailedPrecondition`]; the server faults with [`Code::Internal`]
+    pub fn code(&self) -> Code {
+        match self {
+            Error::Io(_) | Error::Fmt(_) | Error::Other(_) | Error::MissingConfiguration(_) => {
+                Code::Internal
+            }
+            Error::NotReady => Code::Unavailable,
+            Error::Decode { source, .. } => source.code(),
+            Error::Bincode(_)
+            | Error::Prost(_)
+            | Error::MissingField(_)
+            | Error::InvalidLength { .. }
+            | Error::InvalidEncoding(_)
+            | Error::InvalidProof
+            | Error::Field(_)
+            | Error::FeeOutput
+            | Error::InvalidPoint
+            | Error::InvalidParameters
+            | Error::MaximumNotes
+            | Error::InvalidShape
+            | Error::InvalidMnemonic
+            | Error::InvalidMnemonicChecksum
+            | Error::InvalidBatch(_) => Code::InvalidArgument,
+            Error::DecryptionFailed => Code::PermissionDenied,
+            Error::NotFound => Code::NotFound,
+            Error::UnknownMerkleRoot(_) | Error::SecretKeyRequired => Code::FailedPrecondition,
+            Error::ValueOverflow => Code::OutOfRange,
+            Error::DoubleSpending => Code::AlreadyExists,
+            Error::TransactionNotPrepared | Error::InsufficientFunds => Code::FailedPrecondition,
+        }
+    }
+
+    /// Constant that identifies the error on the [`ErrorInfo`] details. A decoding failure is
+    /// identified by its innermost source
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "IO",
+            Error::Fmt(_) => "FMT",
+            Error::Bincode(_) => "BINCODE",
+            Error::Prost(_) => "PROST",
+            Error::Field(_) => "FIELD",
+            Error::Other(_) => "OTHER",
+            Error::Decode { source, .. } => source.reason(),
+            Error::MissingField(_) => "MISSING_FIELD",
+            Error::InvalidLength { .. } => "INVALID_LENGTH",
+            Error::InvalidEncoding(_) => "INVALID_ENCODING",
+            Error::InvalidProof => "INVALID_PROOF",
+            Error::MissingConfiguration(_) => "MISSING_CONFIGURATION",
+            Error::NotReady => "NOT_READY",
+            Error::TransactionNotPrepared => "TRANSACTION_NOT_PREPARED",
+            Error::FeeOutput => "FEE_OUTPUT",
+            Error::InvalidPoint => "INVALID_POINT",
+            Error::InvalidParameters => "INVALID_PARAMETERS",
+            Error::MaximumNotes => "MAXIMUM_NOTES",
+            Error::InvalidShape => "INVALID_SHAPE",
+            Error::UnknownMerkleRoot(_) => "UNKNOWN_MERKLE_ROOT",
+            Error::NotFound => "NOT_FOUND",
+            Error::DoubleSpending => "DOUBLE_SPENDING",
+            Error::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            Error::ValueOverflow => "VALUE_OVERFLOW",
+            Error::InvalidMnemonic => "INVALID_MNEMONIC",
+            Error::InvalidMnemonicChecksum => "INVALID_MNEMONIC_CHECKSUM",
+            Error::DecryptionFailed => "DECRYPTION_FAILED",
+            Error::SecretKeyRequired => "SECRET_KEY_REQUIRED",
+            Error::InvalidBatch(_) => "INVALID_BATCH",
+        }
+    }
+
+    /// Structured details of the error, to be decoded by the clients. The path of the fields that
+    /// failed to decode is reported as the `field` metadata
+    pub fn error_info(&self) -> ErrorInfo {
+        let mut metadata = HashMap::new();
+        let mut fields = vec![];
+        let mut e = self;
+
+        while let Error::Decode { field, source } = e {
+            fields.push(*field);
+            e = source.as_ref();
+        }
+
+        match e {
+            Error::InvalidBatch(invalid) => {
+                let invalid: Vec<String> = invalid.iter().map(|i| i.to_string()).collect();
+                metadata.insert("invalid".to_string(), invalid.join(","));
+            }
+            Error::MissingField(field) | Error::InvalidEncoding(field) => fields.push(*field),
+            Error::UnknownMerkleRoot(input) => {
+                metadata.insert("input".to_string(), input.to_string());
+            }
+            Error::InvalidLength {
+                field,
+                expected,
+                actual,
+            } => {
+                fields.push(*field);
+                metadata.insert("expected".to_string(), expected.to_string());
+                metadata.insert("actual".to_string(), actual.to_string());
+            }
+            _ => (),
+        }
+
+        if !fields.is_empty() {
+            metadata.insert("field".to_string(), fields.join("/"));
+        }
+
+        ErrorInfo {
+            reason: self.reason().to_string(),
+            domain: ERROR_DOMAIN.to_string(),
+            metadata,
+        }
+    }
+}
+
+impl From<Error> for Status {
+    fn from(e: Error) -> Self {
+        let code = e.code();
+        let message = format!("{}", e);
+
+        let mut info = vec![];
+        let mut details = vec![];
+
+        // Encoding on a vector never fails
+        e.error_info().encode(&mut info).ok();
+        RpcStatus {
+            code: code as i32,
+            message: message.clone(),
+            details: vec![Any {
+                type_url: ERROR_INFO_TYPE_URL.to_string(),
+                value: info,
+            }],
+        }
+        .encode(&mut details)
+        .ok();
+
+        Status::with_details(code, message, Bytes::from(details))
+    }
+}
+
+/// Decode the [`ErrorInfo`] of a status created from an [`Error`]
+pub fn error_info(status: &Status) -> Option<ErrorInfo> {
+    RpcStatus::decode(status.details())
+        .ok()?
+        .details
+        .into_iter()
+        .find(|d| d.type_url == ERROR_INFO_TYPE_URL)
+        .and_then(|d| ErrorInfo::decode(d.value.as_slice()).ok())
+}
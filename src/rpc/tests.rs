@@ -1,9 +1,22 @@
+use crate::error::DebugError;
 use crate::{
-    db, rpc, MerkleProofProvider, NoteGenerator, NoteVariant, ObfuscatedNote, SecretKey,
-    Transaction, TransactionItem, TransparentNote,
+    crypto, db, rpc, BlsScalar, Error, MerkleProofProvider, NoteGenerator, NoteVariant, NotesDb,
+    ObfuscatedNote, PublicKey, SecretKey, Transaction, TransactionItem, TransparentNote,
+    ValidationError, ViewKey,
 };
 
+use std::convert::TryFrom;
+use std::net::TcpListener;
+use std::time::Duration;
+
 use kelvin::Blake2b;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+use crate::rpc::contract_call_tx::ContractCall;
+use crate::rpc::crypto_client::CryptoClient;
+use crate::rpc::hash_request::Request;
+use crate::rpc::rusk_client::RuskClient;
 
 #[test]
 #[ignore]
@@ -71,3 +84,304 @@ fn rpc_transaction() {
     assert_eq!(3, transaction.fee().value());
     transaction.verify().unwrap();
 }
+
+/// Serve the notes db on a free loopback port, and return a channel connected to it
+async fn loopback_channel(db: NotesDb) -> Channel {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    tokio::spawn(rpc::serve(addr, db));
+
+    let endpoint = Endpoint::from_shared(format!("http://{}", addr)).unwrap();
+    loop {
+        match endpoint.connect().await {
+            Ok(channel) => return channel,
+            Err(_) => tokio::time::delay_for(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+#[tokio::test]
+async fn rpc_server() {
+    let mut db = NotesDb::default();
+
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+    let notes: Vec<NoteVariant> = vec![
+        ObfuscatedNote::output(&pk, 100).0.into(),
+        TransparentNote::output(&pk, 50).0.into(),
+        ObfuscatedNote::output(&SecretKey::default().public_key(), 1000)
+            .0
+            .into(),
+    ];
+    for note in notes {
+        db.store_unspent_note(note).unwrap();
+    }
+
+    let mut client = RuskClient::new(loopback_channel(db).await);
+
+    client.echo(rpc::EchoRequest {}).await.unwrap();
+
+    let request = rpc::GenerateSecretKeyRequest {
+        b: b"seed".to_vec(),
+    };
+    let generated = client
+        .generate_secret_key(request.clone())
+        .await
+        .unwrap()
+        .into_inner();
+    let generated_sk = SecretKey::try_from(generated.sk.clone().unwrap()).unwrap();
+    assert_eq!(SecretKey::from(&b"seed"[..]), generated_sk);
+    assert_eq!(
+        generated,
+        client
+            .generate_secret_key(request)
+            .await
+            .unwrap()
+            .into_inner()
+    );
+
+    let keys = client
+        .keys(rpc::SecretKey::from(sk))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(sk.view_key(), ViewKey::try_from(keys.vk.unwrap()).unwrap());
+    assert_eq!(pk, PublicKey::try_from(keys.pk.unwrap()).unwrap());
+
+    let vk = rpc::ViewKey::from(sk.view_key());
+    let owned = client
+        .full_scan_owned_notes(vk.clone())
+        .await
+        .unwrap()
+        .into_inner();
+    let values: Vec<u64> = owned.notes.iter().map(|n| n.value).collect();
+    assert_eq!(vec![100, 50], values);
+
+    let balance = client
+        .get_balance(rpc::GetBalanceRequest {
+            vk: Some(vk.clone()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(150, balance.balance);
+
+    let recipient = SecretKey::default();
+    let tx = client
+        .new_transaction(rpc::NewTransactionRequest {
+            sk: Some(sk.into()),
+            recipient: Some(recipient.public_key().into()),
+            value: 120,
+            fee: 1,
+            obfuscated: true,
+            data: b"data".to_vec(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(2, tx.inputs.len());
+    assert_eq!(b"data".to_vec(), tx.data);
+
+    let call = rpc::ContractCallTx {
+        contract_call: Some(ContractCall::Tx(tx.clone())),
+    };
+    let verified = client
+        .verify_transaction(call.clone())
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(verified.verified);
+
+    // The spent status of the confirmed notes of the sender can't be checked with its view key
+    let mempool = rpc::CalculateMempoolBalanceRequest {
+        vk: Some(vk),
+        txs: vec![call.clone()],
+    };
+    let status = client.calculate_mempool_balance(mempool).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+    assert_eq!(
+        "SECRET_KEY_REQUIRED",
+        rpc::status::error_info(&status).unwrap().reason
+    );
+
+    // The recipient owns only the pending output
+    let mempool = rpc::CalculateMempoolBalanceRequest {
+        vk: Some(recipient.view_key().into()),
+        txs: vec![call],
+    };
+    let balance = client
+        .calculate_mempool_balance(mempool)
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(120, balance.balance);
+
+    let mempool = rpc::CalculateMempoolBalanceRequest {
+        vk: None,
+        txs: vec![],
+    };
+    let status = client.calculate_mempool_balance(mempool).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+
+    let mut tampered = tx;
+    tampered.inputs.truncate(1);
+    let call = rpc::ContractCallTx {
+        contract_call: Some(ContractCall::Tx(tampered)),
+    };
+    let verified = client.verify_transaction(call).await.unwrap().into_inner();
+    assert!(!verified.verified);
+
+    let call = rpc::ContractCallTx {
+        contract_call: None,
+    };
+    let status = client.verify_transaction(call).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    let info = rpc::status::error_info(&status).unwrap();
+    assert_eq!("MISSING_FIELD", info.reason);
+    assert_eq!("ContractCallTx.tx", info.metadata["field"]);
+
+    let status = client
+        .get_balance(rpc::GetBalanceRequest { vk: None })
+        .await
+        .unwrap_err();
+    let info = rpc::status::error_info(&status).unwrap();
+    assert_eq!("MISSING_FIELD", info.reason);
+    assert_eq!("GetBalanceRequest.vk", info.metadata["field"]);
+
+    let insufficient = rpc::NewTransactionRequest {
+        sk: Some(sk.into()),
+        recipient: Some(pk.into()),
+        value: 1000,
+        fee: 1,
+        obfuscated: false,
+        data: vec![],
+    };
+    let status = client.new_transaction(insufficient).await.unwrap_err();
+    assert_eq!(Code::FailedPrecondition, status.code());
+    assert_eq!(
+        "INSUFFICIENT_FUNDS",
+        rpc::status::error_info(&status).unwrap().reason
+    );
+}
+
+#[test]
+fn status_mapping() {
+    let cases = vec![
+        (Error::InvalidParameters, Code::InvalidArgument),
+        (Error::InvalidPoint, Code::InvalidArgument),
+        (Error::NotFound, Code::NotFound),
+        (Error::DoubleSpending, Code::AlreadyExists),
+        (Error::InsufficientFunds, Code::FailedPrecondition),
+        (Error::generic("bottom"), Code::Internal),
+        (Error::InvalidProof, Code::InvalidArgument),
+        (Error::NotFound.context("Note.pos"), Code::NotFound),
+        (Error::InvalidShape, Code::InvalidArgument),
+        (Error::UnknownMerkleRoot(1), Code::FailedPrecondition),
+        (Error::SecretKeyRequired, Code::FailedPrecondition),
+    ];
+
+    for (error, code) in cases {
+        let reason = error.reason();
+        let status = Status::from(error);
+        assert_eq!(code, status.code());
+
+        let info = rpc::status::error_info(&status).unwrap();
+        assert_eq!(reason, info.reason);
+        assert_eq!(rpc::status::ERROR_DOMAIN, info.domain);
+    }
+
+    let status = Status::from(Error::InvalidBatch(vec![0, 2]));
+    let info = rpc::status::error_info(&status).unwrap();
+    assert_eq!("0,2", info.metadata["invalid"]);
+
+    let status =
+        Status::from(Error::MissingField("TransactionOutput.note").context("Transaction.fee"));
+    let info = rpc::status::error_info(&status).unwrap();
+    assert_eq!(Code::InvalidArgument, status.code());
+    assert_eq!("MISSING_FIELD", info.reason);
+    assert_eq!(
+        "Transaction.fee/TransactionOutput.note",
+        info.metadata["field"]
+    );
+
+    let status = Status::from(Error::from(ValidationError::UnknownMerkleRoot(1)));
+    let info = rpc::status::error_info(&status).unwrap();
+    assert_eq!("UNKNOWN_MERKLE_ROOT", info.reason);
+    assert_eq!("1", info.metadata["input"]);
+
+    assert!(rpc::status::error_info(&Status::internal("no details")).is_none());
+
+    // The generic errors keep their source
+    match Error::generic("bottom") {
+        Error::Other(e) => {
+            assert_eq!("\"bottom\"", e.to_string());
+            assert_eq!("bottom", e.downcast_ref::<DebugError<&str>>().unwrap().0);
+        }
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn rpc_hash() {
+    let mut client = CryptoClient::new(loopback_channel(NotesDb::default()).await);
+
+    let inputs = vec![vec![0x01], vec![0x02, 0x00]];
+    let request = rpc::HashRequest {
+        request: Some(Request::BinInputs(rpc::BinaryHashRequest {
+            inputs: inputs.clone(),
+        })),
+    };
+    let hash = client.hash(request).await.unwrap().into_inner();
+    let hash = BlsScalar::try_from(hash.hash.unwrap()).unwrap();
+    let scalars = [
+        BlsScalar::one(),
+        BlsScalar::one(),
+        BlsScalar::from(2u64),
+        BlsScalar::from(2u64),
+    ];
+    assert_eq!(crypto::sponge_hash(&scalars), hash);
+    assert_eq!(crypto::hash_bytes(inputs.as_slice()).unwrap(), hash);
+
+    let tx = Transaction::default();
+    let request = rpc::HashRequest {
+        request: Some(Request::TxInputs(rpc::TransactionHashRequest {
+            txs: vec![rpc::Transaction::try_from(tx.clone()).unwrap()],
+        })),
+    };
+    let hash = client.hash(request.clone()).await.unwrap().into_inner();
+    assert_eq!(
+        crypto::sponge_hash(&[tx.hash()]),
+        BlsScalar::try_from(hash.hash.unwrap()).unwrap()
+    );
+
+    let mut txs = request;
+    if let Some(Request::TxInputs(r)) = txs.request.as_mut() {
+        r.txs.push(r.txs[0].clone());
+    }
+    let hash = client.hash(txs).await.unwrap().into_inner();
+    assert_eq!(
+        crypto::sponge_hash(&[tx.hash(), tx.hash()]),
+        BlsScalar::try_from(hash.hash.unwrap()).unwrap()
+    );
+
+    let empty = rpc::HashRequest {
+        request: Some(Request::BinInputs(rpc::BinaryHashRequest {
+            inputs: vec![],
+        })),
+    };
+    let status = client.hash(empty).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    assert_eq!(
+        "BinaryHashRequest.inputs",
+        rpc::status::error_info(&status).unwrap().metadata["field"]
+    );
+
+    let status = client
+        .hash(rpc::HashRequest { request: None })
+        .await
+        .unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+}
@@ -1,5 +1,6 @@
 use crate::{
     rpc, utils, BlsScalar, Error, JubJubAffine, JubJubExtended, JubJubScalar, Nonce, Nullifier,
+    NONCEBYTES,
 };
 
 use std::convert::TryFrom;
@@ -118,6 +119,10 @@ impl TryFrom<rpc::Nonce> for Nonce {
     type Error = Error;
 
     fn try_from(nonce: rpc::Nonce) -> Result<Self, Self::Error> {
-        Nonce::from_slice(nonce.bs.as_slice()).ok_or(Error::InvalidParameters)
+        Nonce::from_slice(nonce.bs.as_slice()).ok_or(Error::InvalidLength {
+            field: "Nonce",
+            expected: NONCEBYTES,
+            actual: nonce.bs.len(),
+        })
     }
 }
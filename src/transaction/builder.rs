@@ -0,0 +1,164 @@
+use crate::{
+    utils, Error, MerkleProofProvider, Note, NoteGenerator, NoteType, NoteVariant, ObfuscatedNote,
+    PublicKey, SecretKey, Transaction, TransparentNote, MAX_INPUT_NOTES_PER_TRANSACTION,
+    MAX_OUTPUT_NOTES_PER_TRANSACTION,
+};
+
+use tracing::warn;
+
+/// Create balanced transactions from the unspent notes of a sender.
+///
+/// The inputs are selected from the provided notes, and the remainder is sent back to the sender
+/// as a change output.
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    sk: SecretKey,
+    notes: Vec<NoteVariant>,
+    outputs: Vec<(PublicKey, u64, NoteType)>,
+    fee: u64,
+}
+
+impl TransactionBuilder {
+    /// Create a new builder for the owner of the provided secret key
+    pub fn new(sk: SecretKey) -> Self {
+        Self {
+            sk,
+            notes: vec![],
+            outputs: vec![],
+            fee: 0,
+        }
+    }
+
+    /// Append unspent notes of the sender as candidates for the inputs
+    pub fn notes<I: IntoIterator<Item = NoteVariant>>(mut self, notes: I) -> Self {
+        self.notes.extend(notes);
+        self
+    }
+
+    /// Append a recipient of the transaction, with an obfuscated output
+    pub fn output(mut self, pk: PublicKey, value: u64) -> Self {
+        self.outputs.push((pk, value, NoteType::Obfuscated));
+        self
+    }
+
+    /// Append a recipient of the transaction, with a transparent output
+    pub fn transparent_output(mut self, pk: PublicKey, value: u64) -> Self {
+        self.outputs.push((pk, value, NoteType::Transparent));
+        self
+    }
+
+    /// Set the fee of the transaction
+    pub fn fee(mut self, fee: u64) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Select the inputs, fetch their merkle openings and return the balanced transaction.
+    ///
+    /// The transaction is not proven.
+    pub fn build<P: MerkleProofProvider>(&self, provider: &P) -> Result<Transaction, Error> {
+        let vk = self.sk.view_key();
+
+        let target = self
+            .outputs
+            .iter()
+            .try_fold(self.fee, |sum, (_, value, _)| sum.checked_add(*value))
+            .ok_or(Error::ValueOverflow)?;
+
+        if self.outputs.len() > MAX_OUTPUT_NOTES_PER_TRANSACTION {
+            return Err(Error::MaximumNotes);
+        }
+
+        // The notes that can't be decrypted can't be spent either, so they are skipped
+        let notes: Vec<(NoteVariant, u64)> = self
+            .notes
+            .iter()
+            .filter(|n| n.is_owned_by(&vk))
+            .filter_map(|n| match n.try_value(&vk) {
+                Ok(value) => Some((*n, value)),
+                Err(e) => {
+                    warn!("Skipping the undecryptable note {}: {}", n.idx(), e);
+                    None
+                }
+            })
+            .collect();
+
+        let inputs = select_notes(notes, target)?;
+        let change = utils::checked_sum(inputs.iter().map(|(_, value)| *value))?
+            .checked_sub(target)
+            .ok_or(Error::InsufficientFunds)?;
+
+        if change > 0 && self.outputs.len() >= MAX_OUTPUT_NOTES_PER_TRANSACTION {
+            return Err(Error::MaximumNotes);
+        }
+
+        let mut tx = Transaction::default();
+
+        for (note, _) in inputs {
+            let merkle_opening = provider.opening(&note)?;
+            tx.push_input(note.to_transaction_input(merkle_opening, self.sk)?)?;
+        }
+
+        for (pk, value, note_type) in self.outputs.iter() {
+            let output = match note_type {
+                NoteType::Transparent => {
+                    let (note, blinding_factor) = TransparentNote::output(pk, *value);
+                    note.to_transaction_output(*value, blinding_factor, *pk)
+                }
+                NoteType::Obfuscated => {
+                    let (note, blinding_factor) = ObfuscatedNote::output(pk, *value);
+                    note.to_transaction_output(*value, blinding_factor, *pk)
+                }
+            };
+
+            tx.push_output(output)?;
+        }
+
+        if change > 0 {
+            let pk = self.sk.public_key();
+            let (note, blinding_factor) = ObfuscatedNote::output(&pk, change);
+            tx.push_output(note.to_transaction_output(change, blinding_factor, pk))?;
+        }
+
+        let pk = PublicKey::default();
+        let (note, blinding_factor) = TransparentNote::output(&pk, self.fee);
+        tx.set_fee(note.to_transaction_output(self.fee, blinding_factor, pk));
+
+        Ok(tx)
+    }
+}
+
+/// Select the fewest notes that cover the target value, and the smallest last note so the change
+/// is minimized
+fn select_notes(
+    mut notes: Vec<(NoteVariant, u64)>,
+    target: u64,
+) -> Result<Vec<(NoteVariant, u64)>, Error> {
+    notes.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let total = notes
+        .iter()
+        .fold(0u128, |sum, (_, value)| sum + *value as u128);
+    // At least one input is required by the circuits, even for zero-value transactions
+    if notes.is_empty() || total < target as u128 {
+        return Err(Error::InsufficientFunds);
+    }
+
+    let mut sum = 0u64;
+    for k in 0..MAX_INPUT_NOTES_PER_TRANSACTION.min(notes.len()) {
+        let missing = target - sum;
+
+        // The notes are sorted descending, so the last one that covers the missing value is the
+        // smallest
+        if let Some(last) = notes[k..].iter().rposition(|(_, value)| *value >= missing) {
+            let mut selected = notes[..k].to_vec();
+            selected.push(notes[k + last]);
+
+            return Ok(selected);
+        }
+
+        sum += notes[k].1;
+    }
+
+    Err(Error::MaximumNotes)
+}
@@ -1,3 +1,4 @@
+use crate::error::ResultExt;
 use crate::{
     crypto, db, rpc, BlsScalar, Error, JubJubScalar, MerkleProofProvider, Nonce, Note,
     NoteGenerator, NoteVariant, Nullifier, PublicKey, SecretKey, TransparentNote,
@@ -114,8 +115,16 @@ impl TransactionInput {
         item: rpc::TransactionInput,
     ) -> Result<Self, Error> {
         let mut txi = TransactionInput::default();
-        txi.nullifier = item.nullifier.unwrap().try_into()?;
-        txi.merkle_root = item.merkle_root.unwrap().try_into()?;
+        txi.nullifier = item
+            .nullifier
+            .ok_or(Error::MissingField("TransactionInput.nullifier"))?
+            .try_into()
+            .context("TransactionInput.nullifier")?;
+        txi.merkle_root = item
+            .merkle_root
+            .ok_or(Error::MissingField("TransactionInput.merkle_root"))?
+            .try_into()
+            .context("TransactionInput.merkle_root")?;
 
         Ok(txi)
     }
@@ -280,8 +289,8 @@ impl Ord for TransactionOutput {
 
 impl From<TransactionInput> for rpc::TransactionInput {
     fn from(item: TransactionInput) -> rpc::TransactionInput {
-        let merkle_root = *item.merkle_opening.root();
-        let merkle_root = Some(merkle_root.into());
+        // The opening is cleared with the sensitive info, but the root is preserved
+        let merkle_root = Some(item.merkle_root.into());
 
         let nullifier = Some(item.nullifier.into());
 
@@ -309,8 +318,16 @@ impl TryFrom<rpc::TransactionInput> for TransactionInput {
     type Error = Error;
 
     fn try_from(txi: rpc::TransactionInput) -> Result<Self, Self::Error> {
-        let nullifier = txi.nullifier.unwrap_or_default().try_into()?;
-        let merkle_root = txi.merkle_root.unwrap_or_default().try_into()?;
+        let nullifier = txi
+            .nullifier
+            .unwrap_or_default()
+            .try_into()
+            .context("TransactionInput.nullifier")?;
+        let merkle_root = txi
+            .merkle_root
+            .unwrap_or_default()
+            .try_into()
+            .context("TransactionInput.merkle_root")?;
 
         let merkle_opening = Default::default();
         let value = Default::default();
@@ -334,18 +351,21 @@ impl TryFrom<rpc::TransactionOutput> for TransactionOutput {
     fn try_from(txo: rpc::TransactionOutput) -> Result<Self, Self::Error> {
         let note = txo
             .note
-            .ok_or(Error::InvalidParameters)
-            .and_then(|n| n.try_into())?;
+            .ok_or(Error::MissingField("TransactionOutput.note"))?
+            .try_into()
+            .context("TransactionOutput.note")?;
 
         let pk = txo
             .pk
-            .ok_or(Error::InvalidParameters)
-            .and_then(|k| k.try_into())?;
+            .ok_or(Error::MissingField("TransactionOutput.pk"))?
+            .try_into()
+            .context("TransactionOutput.pk")?;
 
         let blinding_factor = txo
             .blinding_factor
-            .ok_or(Error::InvalidParameters)?
-            .try_into()?;
+            .ok_or(Error::MissingField("TransactionOutput.blinding_factor"))?
+            .try_into()
+            .context("TransactionOutput.blinding_factor")?;
 
         Ok(TransactionOutput::new(note, txo.value, blinding_factor, pk))
     }
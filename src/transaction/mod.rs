@@ -1,14 +1,13 @@
+use crate::error::ResultExt;
 use crate::{
-    crypto, db, rpc, utils, zk, BlsScalar, Error, Note, NoteGenerator, ObfuscatedNote, PublicKey,
-    SecretKey, TransparentNote,
+    crypto, db, rpc, utils, zk, BlsScalar, Error, Note, NoteGenerator, NoteVariant, ObfuscatedNote,
+    PublicKey, SecretKey, TransparentNote, NOTE_SERIALIZED_SIZE,
 };
 
 use std::convert::TryFrom;
 use std::io::{self, Read, Write};
-use std::mem;
-use std::{fmt, ptr};
+use std::{fmt, iter};
 
-use dusk_plonk::proof_system::Proof;
 use kelvin::ByteHash;
 
 use rand::distributions::{Distribution, Standard};
@@ -16,62 +15,56 @@ use rand::Rng;
 
 use bincode::{deserialize, serialize};
 
-pub const MAX_NOTES_PER_TRANSACTION: usize = 1 + 2;
-pub const MAX_INPUT_NOTES_PER_TRANSACTION: usize = 1;
+/// Maximum allowed number of notes per transaction.
+pub const MAX_NOTES_PER_TRANSACTION: usize =
+    MAX_INPUT_NOTES_PER_TRANSACTION + MAX_OUTPUT_NOTES_PER_TRANSACTION;
+pub const MAX_INPUT_NOTES_PER_TRANSACTION: usize = 4;
 pub const MAX_OUTPUT_NOTES_PER_TRANSACTION: usize = 2;
 
-/// Maximum allowed number of notes per transaction.
+/// Version of the transaction wire format
+pub const TX_VERSION: u8 = 1;
 
-/// Serialized bytes size
-pub const TX_SERIALIZED_SIZE: usize = 1684;
+/// Maximum serialized bytes size of a proven transaction with all the input and output slots used
+pub const TX_SERIALIZED_SIZE: usize = 2
+    + zk::SERIALIZED_PROOF_SIZE
+    + 1
+    + 2 * MAX_INPUT_NOTES_PER_TRANSACTION * utils::BLS_SCALAR_SERIALIZED_SIZE
+    + 1
+    + (MAX_OUTPUT_NOTES_PER_TRANSACTION + 1) * NOTE_SERIALIZED_SIZE;
 
+pub use builder::TransactionBuilder;
 pub use item::{TransactionInput, TransactionItem, TransactionOutput};
 
 lazy_static::lazy_static! {
-    static ref DEFAULT_INPUT: TransactionInput = TransactionInput::default();
     static ref DEFAULT_OUTPUT: TransactionOutput = TransactionOutput::default();
 }
 
+/// Balanced transaction construction from unspent notes
+pub mod builder;
 /// Transaction item definitions
 pub mod item;
 
+#[cfg(test)]
+mod tests;
+
 /// A phoenix transaction
+///
+/// The proof is kept in its canonical serialized form, and is decoded only when required
+#[derive(Clone)]
 pub struct Transaction {
     fee: TransactionOutput,
-    idx_inputs: usize,
-    inputs: [TransactionInput; MAX_INPUT_NOTES_PER_TRANSACTION],
-    idx_outputs: usize,
-    outputs: [TransactionOutput; MAX_OUTPUT_NOTES_PER_TRANSACTION],
-    proof: Option<zk::Proof>,
+    inputs: Vec<TransactionInput>,
+    outputs: Vec<TransactionOutput>,
+    proof: Option<Vec<u8>>,
     public_inputs: Option<zk::ZkPublicInputs>,
 }
 
-impl Clone for Transaction {
-    // TODO: we should have a safe way of cloning the proof
-    fn clone(&self) -> Self {
-        unsafe {
-            let p: Option<zk::Proof> = mem::transmute_copy(&self.proof);
-            Transaction {
-                fee: self.fee.clone(),
-                idx_inputs: self.idx_inputs.clone(),
-                inputs: self.inputs.clone(),
-                idx_outputs: self.idx_outputs.clone(),
-                outputs: self.outputs.clone(),
-                proof: p,
-                public_inputs: self.public_inputs.clone(),
-            }
-        }
-    }
-}
-
 impl Default for Transaction {
     fn default() -> Self {
         Self {
             fee: *DEFAULT_OUTPUT,
-            idx_inputs: 0,
-            inputs: [*DEFAULT_INPUT; MAX_INPUT_NOTES_PER_TRANSACTION],
-            idx_outputs: 0,
-            outputs: [*DEFAULT_OUTPUT; MAX_OUTPUT_NOTES_PER_TRANSACTION],
+            inputs: vec![],
+            outputs: vec![],
             proof: None,
             public_inputs: None,
         }
@@ -79,132 +72,30 @@ impl Default for Transaction {
 }
 
 impl Read for Transaction {
-    fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.clear_sensitive_info();
 
-        let mut n = 0;
-
-        // Serialize proof
-        if self.proof.is_some() {
-            let encoded: Vec<u8> = serialize(&self.proof().unwrap())
-                .unwrap_or(vec![0x00u8; zk::SERIALIZED_PROOF_SIZE]);
-
-            let b = (&encoded[..]).read(buf)?;
-            n += b;
-            buf = &mut buf[b..];
-        }
-
-        // Serialize tx inputs (merkle root and nullifier)
-        let inputs = self.idx_inputs.to_le_bytes();
-        let b = (&inputs[..]).read(buf)?;
-        n += b;
-        buf = &mut buf[b..];
-
-        for i in 0..MAX_INPUT_NOTES_PER_TRANSACTION {
-            buf.chunks_mut(utils::BLS_SCALAR_SERIALIZED_SIZE)
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(|c| Ok(c.copy_from_slice(&self.inputs[i].merkle_root.to_bytes()[..])))
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-            buf = &mut buf[utils::BLS_SCALAR_SERIALIZED_SIZE..];
-
-            buf.chunks_mut(utils::BLS_SCALAR_SERIALIZED_SIZE)
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(|c| Ok(c.copy_from_slice(&self.inputs[i].nullifier.s().to_bytes()[..])))
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-            buf = &mut buf[utils::BLS_SCALAR_SERIALIZED_SIZE..];
-        }
-
-        // Serialize tx outputs
-        let outputs = self.idx_outputs.to_le_bytes();
-        let b = (&outputs[..]).read(buf)?;
-        n += b;
-        buf = &mut buf[b..];
-
-        for i in 0..MAX_OUTPUT_NOTES_PER_TRANSACTION {
-            let b = self.outputs[i].read(buf)?;
-            n += b;
-            buf = &mut buf[b..];
+        let bytes = self.to_bytes().map_err::<io::Error, _>(|e| e.into())?;
+        if buf.len() < bytes.len() {
+            return Err(Error::InvalidParameters.into());
         }
 
-        let b = self.fee.read(buf)?;
-        n += b;
+        buf[..bytes.len()].copy_from_slice(bytes.as_slice());
 
-        Ok(n)
+        Ok(bytes.len())
     }
 }
 
 impl Write for Transaction {
-    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
-        let mut n = 0;
-
-        // Deserialize proof
-        let mut proof = [0x00u8; zk::SERIALIZED_PROOF_SIZE];
-        let b = (&mut proof[..]).write(buf)?;
-        let proof: Proof = deserialize(&proof[..])
-            .map_err(|_| Error::InvalidParameters)
-            .map_err::<io::Error, _>(|e| e.into())?;
-        self.proof.replace(proof);
-        n += b;
-        buf = &buf[b..];
-
-        // Deserialize tx inputs (merkle root and nullifier)
-        let mut inputs = 0usize.to_le_bytes();
-        let b = (&mut inputs[..]).write(buf)?;
-        self.idx_inputs = usize::from_le_bytes(inputs);
-        n += b;
-        buf = &buf[b..];
-
-        for i in 0..MAX_INPUT_NOTES_PER_TRANSACTION {
-            let merkle_root = buf
-                .chunks(utils::BLS_SCALAR_SERIALIZED_SIZE)
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(utils::deserialize_bls_scalar)
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-            buf = &buf[utils::BLS_SCALAR_SERIALIZED_SIZE..];
-
-            let nullifier = buf
-                .chunks(utils::BLS_SCALAR_SERIALIZED_SIZE)
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(utils::deserialize_bls_scalar)
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-            buf = &buf[utils::BLS_SCALAR_SERIALIZED_SIZE..];
-
-            self.inputs[i] = TransactionInput::obfuscated(nullifier.into(), merkle_root);
-        }
-
-        // Deserialize tx outputs
-        let mut outputs = 0usize.to_le_bytes();
-        let b = (&mut outputs[..]).write(buf)?;
-        self.idx_outputs = usize::from_le_bytes(outputs);
-        n += b;
-        buf = &buf[b..];
-
-        for i in 0..MAX_OUTPUT_NOTES_PER_TRANSACTION {
-            let b = self.outputs[i].write(buf)?;
-            n += b;
-            buf = &buf[b..];
-        }
-
-        let b = self.fee.write(buf)?;
-        n += b;
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (tx, n) = Self::decode(buf).map_err::<io::Error, _>(|e| e.into())?;
+        *self = tx;
 
         Ok(n)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        for i in 0..MAX_OUTPUT_NOTES_PER_TRANSACTION {
-            self.outputs[i].flush()?;
-        }
-
-        self.fee.flush()
+        Ok(())
     }
 }
 
@@ -275,66 +166,127 @@ impl Transaction {
     pub fn hash(&self) -> BlsScalar {
         // TODO - Maybe improve?
 
-        let mut hash = [BlsScalar::zero(); 2 * MAX_NOTES_PER_TRANSACTION + 1];
-        let mut i = 1;
+        let mut hash = Vec::with_capacity(1 + self.inputs.len() + self.outputs.len());
+        hash.push(self.fee.hash());
 
-        hash[0] = self.fee.hash();
+        let mut inputs = self.inputs.clone();
+        inputs.sort();
+        hash.extend(inputs.iter().map(|item| item.note().hash()));
 
-        let mut items = [TransactionInput::default(); MAX_INPUT_NOTES_PER_TRANSACTION];
+        let mut outputs = self.outputs.clone();
+        outputs.sort();
+        hash.extend(outputs.iter().map(|item| item.note().hash()));
 
-        let max_idx = self.idx_inputs;
-        if max_idx > 0 {
-            items.copy_from_slice(&self.inputs);
-            (&mut items[0..max_idx]).sort();
+        crypto::sponge_hash(hash.as_slice())
+    }
 
-            items[0..max_idx]
-                .iter()
-                .map(|item| item.note().hash())
-                .for_each(|h| {
-                    hash[i] = h;
-                    i += 1;
-                });
+    /// Serialize the transaction in the canonical wire format
+    ///
+    /// `version | proof flag | proof? | inputs | (merkle root | nullifier)* | outputs |
+    /// output note* | fee note`
+    ///
+    /// The counts and the proof flag are single bytes. The sensitive data of the items is never
+    /// serialized.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(TX_SERIALIZED_SIZE);
+        bytes.push(TX_VERSION);
+
+        match self.proof.as_ref() {
+            Some(proof) => {
+                bytes.push(0x01);
+                bytes.extend_from_slice(proof.as_slice());
+            }
+            None => bytes.push(0x00),
         }
 
-        let mut items = [TransactionOutput::default(); MAX_OUTPUT_NOTES_PER_TRANSACTION];
+        bytes.push(self.inputs.len() as u8);
+        for input in self.inputs.iter() {
+            bytes.extend_from_slice(&input.merkle_root.to_bytes()[..]);
+            bytes.extend_from_slice(&input.nullifier.s().to_bytes()[..]);
+        }
+
+        bytes.push(self.outputs.len() as u8);
+        let mut note = [0x00u8; NOTE_SERIALIZED_SIZE];
+        for output in self.outputs.iter().chain(iter::once(&self.fee)) {
+            let mut variant = *output.note();
+            let n = variant.read(&mut note)?;
+            bytes.extend_from_slice(&note[..n]);
+        }
 
-        let max_idx = self.idx_outputs;
-        if max_idx > 0 {
-            items.copy_from_slice(&self.outputs);
-            (&mut items[0..max_idx]).sort();
+        Ok(bytes)
+    }
 
-            items[0..max_idx]
-                .iter()
-                .map(|item| item.note().hash())
-                .for_each(|h| {
-                    hash[i] = h;
-                    i += 1;
-                });
+    /// Deserialize a transaction created with [`Transaction::to_bytes`].
+    ///
+    /// Will fail on unknown versions, and on truncated or trailing bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (tx, n) = Self::decode(bytes)?;
+        if n != bytes.len() {
+            return Err(Error::InvalidLength {
+                field: "Transaction",
+                expected: n,
+                actual: bytes.len(),
+            });
         }
 
-        crypto::sponge_hash(&hash[0..i])
+        Ok(tx)
+    }
+
+    /// Decode a transaction from the beginning of the provided bytes. Return the transaction and
+    /// the number of consumed bytes
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        let mut buf = bytes;
+
+        if utils::take(&mut buf, 1, "Transaction.version")?[0] != TX_VERSION {
+            return Err(Error::InvalidEncoding("Transaction.version"));
+        }
+
+        let mut tx = Transaction::default();
+
+        match utils::take(&mut buf, 1, "Transaction.proof")?[0] {
+            0x00 => (),
+            0x01 => {
+                let proof = utils::take(&mut buf, zk::SERIALIZED_PROOF_SIZE, "Transaction.proof")?;
+                tx.set_proof_bytes(proof.to_vec())?;
+            }
+            _ => return Err(Error::InvalidEncoding("Transaction.proof")),
+        }
+
+        let inputs = utils::take(&mut buf, 1, "Transaction.inputs")?[0];
+        for _ in 0..inputs {
+            let input = take_input(&mut buf).context("Transaction.inputs")?;
+            tx.push_input(input)?;
+        }
+
+        let outputs = utils::take(&mut buf, 1, "Transaction.outputs")?[0];
+        for _ in 0..outputs {
+            let output = take_output(&mut buf).context("Transaction.outputs")?;
+            tx.push_output(output)?;
+        }
+
+        tx.set_fee(take_output(&mut buf).context("Transaction.fee")?);
+
+        Ok((tx, bytes.len() - buf.len()))
     }
 
     /// Append an input to the transaction
     pub fn push_input(&mut self, item: TransactionInput) -> Result<(), Error> {
-        if self.idx_inputs >= MAX_INPUT_NOTES_PER_TRANSACTION {
+        if self.inputs.len() >= MAX_INPUT_NOTES_PER_TRANSACTION {
             return Err(Error::MaximumNotes);
         }
 
-        self.inputs[self.idx_inputs] = item;
-        self.idx_inputs += 1;
+        self.inputs.push(item);
 
         Ok(())
     }
 
     /// Append an output to the transaction
     pub fn push_output(&mut self, item: TransactionOutput) -> Result<(), Error> {
-        if self.idx_outputs >= MAX_OUTPUT_NOTES_PER_TRANSACTION {
+        if self.outputs.len() >= MAX_OUTPUT_NOTES_PER_TRANSACTION {
             return Err(Error::MaximumNotes);
         }
 
-        self.outputs[self.idx_outputs] = item;
-        self.idx_outputs += 1;
+        self.outputs.push(item);
 
         Ok(())
     }
@@ -361,81 +313,66 @@ impl Transaction {
         self.fee = note.to_transaction_output(value, blinding_factor, pk);
     }
 
-    /// All transaction inputs, including the dummy non-pushed ones
-    pub fn all_inputs(&self) -> &[TransactionInput] {
-        &self.inputs[0..MAX_INPUT_NOTES_PER_TRANSACTION]
-    }
-
     /// Transaction inputs
     pub fn inputs(&self) -> &[TransactionInput] {
-        &self.inputs[0..self.idx_inputs]
-    }
-
-    /// All transaction outputs, including the dummy non-pushed ones
-    pub fn all_outputs(&self) -> &[TransactionOutput] {
-        &self.outputs[0..MAX_OUTPUT_NOTES_PER_TRANSACTION]
+        self.inputs.as_slice()
     }
 
     /// Transaction outputs
     pub fn outputs(&self) -> &[TransactionOutput] {
-        &self.outputs[0..self.idx_outputs]
+        self.outputs.as_slice()
+    }
+
+    /// Number of inputs and outputs of the circuit that proves this transaction, if any
+    pub fn circuit_shape(&self) -> Option<(usize, usize)> {
+        zk::circuit_shape(self.inputs.len(), self.outputs.len())
+    }
+
+    /// Transaction outputs, padded with zero-value dummy outputs up to the number of outputs of
+    /// the circuit that proves this transaction
+    pub fn circuit_outputs(&self) -> Vec<TransactionOutput> {
+        let mut outputs = self.outputs.clone();
+
+        if let Some((_, n)) = self.circuit_shape() {
+            outputs.resize(n, *DEFAULT_OUTPUT);
+        }
+
+        outputs
     }
 
     /// Remove a specified transaction input and return it, if present
     pub fn remove_input(&mut self, idx: usize) -> Option<TransactionInput> {
-        if self.idx_inputs == 0 || idx >= self.idx_inputs {
+        if idx >= self.inputs.len() {
             return None;
-        } else if self.idx_inputs == 1 {
-            self.idx_inputs = 0;
-            return Some(self.inputs[0]);
-        }
-
-        self.idx_inputs -= 1;
-        let src = (&mut self.inputs[self.idx_inputs]) as *mut TransactionInput;
-        let dst = (&mut self.inputs[idx]) as *mut TransactionInput;
-        unsafe {
-            ptr::swap(src, dst);
         }
-        self.inputs[self.idx_inputs] = *DEFAULT_INPUT;
 
-        Some(self.inputs[self.idx_inputs])
+        Some(self.inputs.swap_remove(idx))
     }
 
     /// Remove a specified transaction output and return it, if present
     pub fn remove_output(&mut self, idx: usize) -> Option<TransactionOutput> {
-        if self.idx_outputs == 0 || idx >= self.idx_outputs {
+        if idx >= self.outputs.len() {
             return None;
-        } else if self.idx_outputs == 1 {
-            self.idx_outputs = 0;
-            return Some(self.outputs[0]);
         }
 
-        self.idx_outputs -= 1;
-        let src = (&mut self.outputs[self.idx_outputs]) as *mut TransactionOutput;
-        let dst = (&mut self.outputs[idx]) as *mut TransactionOutput;
-        unsafe {
-            ptr::swap(src, dst);
-        }
-        self.outputs[self.idx_outputs] = *DEFAULT_OUTPUT;
-
-        Some(self.outputs[self.idx_outputs])
+        Some(self.outputs.swap_remove(idx))
     }
 
     /// Sort the inputs and outputs
     pub fn sort_items(&mut self) {
-        if self.idx_inputs > 0 {
-            (&mut self.inputs[0..self.idx_inputs]).sort();
-        }
-
-        if self.idx_outputs > 0 {
-            (&mut self.outputs[0..self.idx_outputs]).sort();
-        }
+        self.inputs.sort();
+        self.outputs.sort();
     }
 
-    fn recalculate_pi(&mut self) {
+    /// Sort the items and recompute the public inputs from the current state of the transaction,
+    /// returning them in the order of [`zk::ZkPublicInputs::generate_pi`]
+    fn recalculate_pi(&mut self) -> Vec<BlsScalar> {
         self.sort_items();
         let public_inputs = zk::ZkPublicInputs::from(&*self);
+        let pi = public_inputs.generate_pi();
         self.public_inputs.replace(public_inputs);
+
+        pi
     }
 
     /// Perform the zk proof, and save internally the created r1cs circuit and the commitment
@@ -445,28 +382,52 @@ impl Transaction {
     ///
     /// The transaction items will be sorted for verification correctness
     pub fn prove(&mut self) -> Result<(), Error> {
-        if self.idx_inputs > MAX_INPUT_NOTES_PER_TRANSACTION
-            || self.idx_outputs > MAX_OUTPUT_NOTES_PER_TRANSACTION
+        if self.inputs.len() > MAX_INPUT_NOTES_PER_TRANSACTION
+            || self.outputs.len() > MAX_OUTPUT_NOTES_PER_TRANSACTION
         {
             return Err(Error::MaximumNotes);
         }
 
         self.recalculate_pi();
 
-        let proof = zk::prove(self);
-        self.proof.replace(proof);
+        let proof = zk::prove(self)?;
+        self.set_proof(&proof)
+    }
 
-        Ok(())
+    /// Return the canonical bytes of the transaction proof created via [`Transaction::prove`]
+    pub fn proof(&self) -> Option<&[u8]> {
+        self.proof.as_ref().map(|p| p.as_slice())
     }
 
-    /// Return the transaction proof created via [`Transaction::prove`]
-    pub fn proof(&self) -> Option<&zk::Proof> {
-        self.proof.as_ref()
+    /// Decode the transaction proof created via [`Transaction::prove`]
+    pub fn decode_proof(&self) -> Result<Option<zk::Proof>, Error> {
+        self.proof
+            .as_ref()
+            .map(|p| deserialize(p.as_slice()).context("Transaction.proof"))
+            .transpose()
     }
 
     /// Replace the current proof, if any
-    pub fn set_proof(&mut self, proof: zk::Proof) {
+    pub fn set_proof(&mut self, proof: &zk::Proof) -> Result<(), Error> {
+        let proof = serialize(proof)?;
+
+        self.set_proof_bytes(proof)
+    }
+
+    /// Replace the current proof with its canonical bytes. The proof will be decoded only when
+    /// the transaction is verified
+    pub fn set_proof_bytes(&mut self, proof: Vec<u8>) -> Result<(), Error> {
+        if proof.len() != zk::SERIALIZED_PROOF_SIZE {
+            return Err(Error::InvalidLength {
+                field: "Transaction.proof",
+                expected: zk::SERIALIZED_PROOF_SIZE,
+                actual: proof.len(),
+            });
+        }
+
         self.proof.replace(proof);
+
+        Ok(())
     }
 
     /// Remove all the sensitive info from the transaction used to build the zk proof so it can be
@@ -486,23 +447,61 @@ impl Transaction {
     /// Doesn't depend on the transaction items secret data. Depends only on the constructed
     /// circuit and commitment points.
     ///
-    /// The transaction items will be sorted for verification correctness
+    /// The transaction items will be sorted for verification correctness, and the public inputs
+    /// recomputed, so a transaction mutated after its proof never verifies against stale ones
     pub fn verify(&mut self) -> Result<(), Error> {
-        if self.public_inputs.is_none() {
-            self.recalculate_pi();
+        let pi = self.recalculate_pi();
+
+        let proof = self.decode_proof()?.ok_or(Error::InvalidProof)?;
+
+        if zk::verify(&proof, pi.as_slice())? {
+            Ok(())
+        } else {
+            Err(Error::InvalidProof)
+        }
+    }
+
+    /// Verify a batch of previously proven transactions, such as the transactions of a block.
+    ///
+    /// The proofs are checked independently via [`zk::verify_batch`], so the batch is faster than
+    /// sequential verification only by running in parallel.
+    ///
+    /// Fail with [`Error::InvalidBatch`] reporting the positions of all the transactions with a
+    /// missing or invalid proof
+    ///
+    /// The transaction items will be sorted for verification correctness, and the public inputs
+    /// recomputed as in [`Transaction::verify`]
+    pub fn verify_batch(transactions: &mut [Transaction]) -> Result<(), Error> {
+        let mut invalid = vec![];
+        let mut batch = Vec::with_capacity(transactions.len());
+
+        for (i, tx) in transactions.iter_mut().enumerate() {
+            let pi = tx.recalculate_pi();
+
+            match tx.decode_proof() {
+                Ok(Some(proof)) => batch.push((i, proof, pi)),
+                _ => invalid.push(i),
+            }
         }
 
-        let pi = self.public_inputs.ok_or(Error::InvalidParameters)?;
-        // TODO: this should be updated once we know the positions of the public inputs
-        // let pi = pi.generate_pi();
-        let pi = vec![];
+        let proofs: Vec<(&zk::Proof, &[BlsScalar])> = batch
+            .iter()
+            .map(|(_, proof, pi)| (proof, pi.as_slice()))
+            .collect();
 
-        let proof = self.proof.as_ref().ok_or(Error::Generic)?;
+        match zk::verify_batch(proofs.as_slice()) {
+            Ok(()) => (),
+            Err(Error::InvalidBatch(failed)) => {
+                invalid.extend(failed.into_iter().map(|f| batch[f].0));
+                invalid.sort_unstable();
+            }
+            Err(e) => return Err(e),
+        }
 
-        if zk::verify(proof, pi.as_slice()) {
+        if invalid.is_empty() {
             Ok(())
         } else {
-            Err(Error::Generic)
+            Err(Error::InvalidBatch(invalid))
         }
     }
 
@@ -523,14 +522,16 @@ impl Transaction {
                 TransactionInput::try_from_rpc_transaction_input(db, i.clone())
                     .and_then(|i| transaction.push_input(i))
             })
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<_, _>>()
+            .context("Transaction.inputs")?;
 
         outputs
             .iter()
             .map(|o| {
                 TransactionOutput::try_from(o.clone()).and_then(|o| transaction.push_output(o))
             })
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<_, _>>()
+            .context("Transaction.outputs")?;
 
         let pk = PublicKey::default();
         let (fee, blinding_factor) = TransparentNote::output(&pk, fee_value);
@@ -551,7 +552,7 @@ impl Transaction {
         let mut transaction = Transaction::default();
 
         if let Some(f) = tx.fee {
-            transaction.set_fee(TransactionOutput::try_from(f)?);
+            transaction.set_fee(TransactionOutput::try_from(f).context("Transaction.fee")?);
         }
 
         tx.inputs
@@ -560,23 +561,50 @@ impl Transaction {
                 TransactionInput::try_from_rpc_transaction_input(&db, i)
                     .and_then(|i| transaction.push_input(i))
             })
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<_, _>>()
+            .context("Transaction.inputs")?;
 
         tx.outputs
             .into_iter()
             .map(|o| TransactionOutput::try_from(o).and_then(|o| transaction.push_output(o)))
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<_, _>>()
+            .context("Transaction.outputs")?;
 
-        let proof = tx.proof;
-        if !proof.is_empty() {
-            let proof = deserialize(proof.as_slice()).map_err(|_| Error::InvalidParameters)?;
-            transaction.set_proof(proof);
+        if !tx.proof.is_empty() {
+            transaction
+                .set_proof_bytes(tx.proof)
+                .context("Transaction.proof")?;
         }
 
         Ok(transaction)
     }
 }
 
+/// Split a serialized obfuscated input from the beginning of the buffer
+fn take_input(buf: &mut &[u8]) -> Result<TransactionInput, Error> {
+    let merkle_root = utils::take(buf, utils::BLS_SCALAR_SERIALIZED_SIZE, "BlsScalar")
+        .and_then(utils::deserialize_bls_scalar)
+        .context("TransactionInput.merkle_root")?;
+    let nullifier = utils::take(buf, utils::BLS_SCALAR_SERIALIZED_SIZE, "BlsScalar")
+        .and_then(utils::deserialize_bls_scalar)
+        .context("TransactionInput.nullifier")?;
+
+    Ok(TransactionInput::obfuscated(nullifier.into(), merkle_root))
+}
+
+/// Split a serialized output note from the beginning of the buffer
+fn take_output(buf: &mut &[u8]) -> Result<TransactionOutput, Error> {
+    let size = buf
+        .first()
+        .and_then(|tag| NoteVariant::serialized_size(*tag))
+        .ok_or(Error::InvalidEncoding("TransactionOutput.note"))?;
+
+    let mut output = TransactionOutput::default();
+    output.write(utils::take(buf, size, "TransactionOutput.note")?)?;
+
+    Ok(output)
+}
+
 impl TryFrom<rpc::Transaction> for Transaction {
     type Error = Error;
 
@@ -584,23 +612,25 @@ impl TryFrom<rpc::Transaction> for Transaction {
         let mut transaction = Transaction::default();
 
         if let Some(f) = tx.fee {
-            transaction.set_fee(TransactionOutput::try_from(f)?);
+            transaction.set_fee(TransactionOutput::try_from(f).context("Transaction.fee")?);
         }
 
         tx.inputs
             .into_iter()
             .map(|i| TransactionInput::try_from(i).and_then(|i| transaction.push_input(i)))
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<_, _>>()
+            .context("Transaction.inputs")?;
 
         tx.outputs
             .into_iter()
             .map(|o| TransactionOutput::try_from(o).and_then(|o| transaction.push_output(o)))
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<_, _>>()
+            .context("Transaction.outputs")?;
 
-        let proof = tx.proof;
-        if !proof.is_empty() {
-            let proof = deserialize(proof.as_slice()).map_err(|_| Error::InvalidParameters)?;
-            transaction.set_proof(proof);
+        if !tx.proof.is_empty() {
+            transaction
+                .set_proof_bytes(tx.proof)
+                .context("Transaction.proof")?;
         }
 
         Ok(transaction)
@@ -611,38 +641,12 @@ impl TryFrom<Transaction> for rpc::Transaction {
     type Error = Error;
 
     fn try_from(tx: Transaction) -> Result<rpc::Transaction, Self::Error> {
-        let inputs = tx
-            .inputs
-            .iter()
-            .filter_map(|i| {
-                if i.value() > 0 {
-                    Some((*i).into())
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        let outputs = tx
-            .outputs
-            .iter()
-            .filter_map(|o| {
-                if o.value() > 0 {
-                    Some((*o).into())
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let inputs = tx.inputs.iter().map(|i| (*i).into()).collect();
+        let outputs = tx.outputs.iter().map(|o| (*o).into()).collect();
 
         let fee = Some(tx.fee.into());
 
-        let proof = tx
-            .proof()
-            .map(|p| serialize(p).map(|b| b.to_vec()))
-            .transpose()
-            .map_err(|_| Error::InvalidParameters)?
-            .unwrap_or_default();
+        let proof = tx.proof.unwrap_or_default();
 
         Ok(rpc::Transaction {
             inputs,
@@ -0,0 +1,487 @@
+use crate::{
+    db, rpc, zk, Error, MerkleProofProvider, NoteGenerator, NoteVariant, ObfuscatedNote, SecretKey,
+    Transaction, TransactionBuilder, TransactionItem, TransparentNote,
+    MAX_INPUT_NOTES_PER_TRANSACTION, TX_SERIALIZED_SIZE, TX_VERSION,
+};
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::{error, mem};
+
+use kelvin::Blake2b;
+
+fn transaction(inputs: &[u64], outputs: &[u64], fee: u64) -> Transaction {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let mut tx = Transaction::default();
+
+    let notes: Vec<(SecretKey, u64)> = inputs
+        .iter()
+        .map(|value| {
+            let sk = SecretKey::default();
+            let pk = sk.public_key();
+            let note: NoteVariant = TransparentNote::output(&pk, *value).0.into();
+
+            (sk, db.store_unspent_note(note).unwrap())
+        })
+        .collect();
+
+    notes.into_iter().for_each(|(sk, idx)| {
+        let note = db.fetch_note(idx).unwrap();
+        let merkle_opening = db.opening(&note).unwrap();
+        tx.push_input(note.to_transaction_input(merkle_opening, sk).unwrap())
+            .unwrap();
+    });
+
+    outputs.iter().for_each(|value| {
+        let sk = SecretKey::default();
+        let pk = sk.public_key();
+        let (note, blinding_factor) = ObfuscatedNote::output(&pk, *value);
+        tx.push_output(note.to_transaction_output(*value, blinding_factor, pk))
+            .unwrap();
+    });
+
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+    let (note, blinding_factor) = TransparentNote::output(&pk, fee);
+    tx.set_fee(note.to_transaction_output(fee, blinding_factor, pk));
+
+    tx
+}
+
+fn proven_transaction() -> Transaction {
+    let mut tx = transaction(&[100], &[95, 2], 3);
+
+    tx.prove().unwrap();
+
+    tx
+}
+
+#[test]
+fn transaction_circuit() {
+    let mut tx = proven_transaction();
+
+    tx.verify().unwrap();
+}
+
+#[test]
+fn transaction_circuit_invalid_public_inputs() {
+    let mut tx = proven_transaction();
+    let mut other = proven_transaction();
+
+    // Attempt to verify the proofs against the public inputs of another transaction
+    mem::swap(&mut tx.proof, &mut other.proof);
+
+    assert!(tx.verify().is_err());
+    assert!(other.verify().is_err());
+}
+
+#[test]
+fn transaction_circuit_mutated() {
+    let mut tx = proven_transaction();
+    let other = proven_transaction();
+
+    // Cache the public inputs, then replace the outputs the proof commits to
+    tx.verify().unwrap();
+    tx.outputs = other.outputs.clone();
+
+    assert!(tx.verify().is_err());
+    match Transaction::verify_batch(&mut [other, tx]) {
+        Err(Error::InvalidBatch(invalid)) => assert_eq!(vec![1], invalid),
+        r => panic!("Unexpected batch verification result {:?}", r),
+    }
+}
+
+#[test]
+fn transaction_circuit_shapes() {
+    assert_eq!(Some((1, 2)), zk::circuit_shape(1, 2));
+    assert_eq!(Some((2, 2)), zk::circuit_shape(2, 1));
+    assert_eq!(Some((4, 2)), zk::circuit_shape(4, 0));
+    assert_eq!(None, zk::circuit_shape(0, 2));
+    assert_eq!(None, zk::circuit_shape(5, 2));
+    assert_eq!(None, zk::circuit_shape(1, 3));
+}
+
+#[test]
+fn transaction_circuit_padded_outputs() {
+    let mut tx = transaction(&[60, 40], &[97], 3);
+
+    assert_eq!(Some((2, 2)), tx.circuit_shape());
+    assert_eq!(2, tx.circuit_outputs().len());
+
+    tx.prove().unwrap();
+    tx.verify().unwrap();
+}
+
+#[test]
+fn transaction_circuit_without_inputs() {
+    let mut tx = transaction(&[], &[97], 3);
+
+    match tx.prove() {
+        Err(Error::InvalidShape) => (),
+        r => panic!("Unexpected prove result {:?}", r),
+    }
+}
+
+#[test]
+fn transaction_rpc_cleared_roots() {
+    let mut tx = transaction(&[60, 40], &[97], 3);
+    let roots: Vec<_> = tx.inputs().iter().map(|i| i.merkle_root).collect();
+    tx.clear_sensitive_info();
+
+    let decoded = Transaction::try_from(rpc::Transaction::try_from(tx).unwrap()).unwrap();
+    let decoded: Vec<_> = decoded.inputs().iter().map(|i| i.merkle_root).collect();
+
+    assert_eq!(roots, decoded);
+}
+
+#[test]
+fn transaction_bytes_roundtrip() {
+    let mut tx = transaction(&[60, 40], &[97], 3);
+    tx.clear_sensitive_info();
+
+    let bytes = tx.to_bytes().unwrap();
+    let decoded = Transaction::from_bytes(bytes.as_slice()).unwrap();
+
+    assert!(decoded.proof().is_none());
+    assert_eq!(tx.inputs().len(), decoded.inputs().len());
+    assert_eq!(tx.outputs().len(), decoded.outputs().len());
+    assert_eq!(bytes, decoded.to_bytes().unwrap());
+
+    tx.inputs()
+        .iter()
+        .zip(decoded.inputs().iter())
+        .for_each(|(a, b)| {
+            assert_eq!(a.nullifier(), b.nullifier());
+            assert_eq!(a.merkle_root, b.merkle_root);
+        });
+
+    tx.outputs()
+        .iter()
+        .zip(decoded.outputs().iter())
+        .for_each(|(a, b)| assert_eq!(a.note(), b.note()));
+    assert_eq!(tx.fee().note(), decoded.fee().note());
+}
+
+#[test]
+fn transaction_bytes_roundtrip_proof() {
+    let mut tx = proven_transaction();
+
+    let bytes = tx.to_bytes().unwrap();
+    assert!(bytes.len() <= TX_SERIALIZED_SIZE);
+
+    let mut decoded = Transaction::from_bytes(bytes.as_slice()).unwrap();
+    assert!(decoded.proof().is_some());
+    assert_eq!(bytes, decoded.to_bytes().unwrap());
+
+    decoded.verify().unwrap();
+
+    let mut buf = [0x00u8; TX_SERIALIZED_SIZE];
+    let n = tx.read(&mut buf).unwrap();
+    assert_eq!(bytes.as_slice(), &buf[..n]);
+
+    let mut written = Transaction::default();
+    assert_eq!(n, written.write(&buf).unwrap());
+    written.verify().unwrap();
+}
+
+#[test]
+fn transaction_bytes_invalid() {
+    let tx = transaction(&[100], &[95, 2], 3);
+    let bytes = tx.to_bytes().unwrap();
+
+    for len in 0..bytes.len() {
+        assert!(Transaction::from_bytes(&bytes[..len]).is_err());
+    }
+
+    // The truncated item is reported
+    let truncated = &bytes[..bytes.len() - 1];
+    match Transaction::from_bytes(truncated).err().unwrap() {
+        Error::Decode { field, source } => {
+            assert_eq!("Transaction.fee", field);
+            match *source {
+                Error::InvalidLength {
+                    field: "TransactionOutput.note",
+                    ..
+                } => (),
+                s => panic!("Unexpected source {:?}", s),
+            }
+        }
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    let mut trailing = bytes.clone();
+    trailing.push(0x00);
+    assert!(Transaction::from_bytes(trailing.as_slice()).is_err());
+
+    let mut version = bytes.clone();
+    version[0] = TX_VERSION + 1;
+    assert!(Transaction::from_bytes(version.as_slice()).is_err());
+
+    let mut flag = bytes.clone();
+    flag[1] = 0x02;
+    assert!(Transaction::from_bytes(flag.as_slice()).is_err());
+
+    let mut inputs = bytes;
+    inputs[2] = (MAX_INPUT_NOTES_PER_TRANSACTION + 1) as u8;
+    assert!(Transaction::from_bytes(inputs.as_slice()).is_err());
+}
+
+#[test]
+fn transaction_rpc_invalid() {
+    let tx = proven_transaction();
+
+    let mut missing = rpc::Transaction::try_from(tx.clone()).unwrap();
+    missing.fee.as_mut().unwrap().note = None;
+
+    let e = Transaction::try_from(missing).err().unwrap();
+    assert_eq!(
+        "Invalid Transaction.fee: Missing TransactionOutput.note",
+        e.to_string()
+    );
+    match &e {
+        Error::Decode { field, source } => {
+            assert_eq!("Transaction.fee", *field);
+            match source.as_ref() {
+                Error::MissingField("TransactionOutput.note") => (),
+                s => panic!("Unexpected source {:?}", s),
+            }
+        }
+        e => panic!("Unexpected conversion error {:?}", e),
+    }
+    assert!(error::Error::source(&e).is_some());
+
+    let mut truncated = rpc::Transaction::try_from(tx).unwrap();
+    truncated.proof.truncate(3);
+
+    match Transaction::try_from(truncated).err() {
+        Some(Error::Decode { field, source }) => {
+            assert_eq!("Transaction.proof", field);
+            match *source {
+                Error::InvalidLength {
+                    expected, actual, ..
+                } => {
+                    assert_eq!(zk::SERIALIZED_PROOF_SIZE, expected);
+                    assert_eq!(3, actual);
+                }
+                s => panic!("Unexpected source {:?}", s),
+            }
+        }
+        e => panic!("Unexpected conversion error {:?}", e),
+    }
+}
+
+#[test]
+fn transaction_clone() {
+    let mut tx = proven_transaction();
+    let mut cloned = tx.clone();
+
+    assert!(tx == cloned);
+    assert_eq!(tx.proof(), cloned.proof());
+
+    drop(tx.clone());
+    tx.verify().unwrap();
+    cloned.verify().unwrap();
+
+    let mut invalid = cloned.clone();
+    assert!(invalid.set_proof_bytes(vec![0x00u8; 3]).is_err());
+    invalid
+        .set_proof_bytes(vec![0xffu8; zk::SERIALIZED_PROOF_SIZE])
+        .unwrap();
+    assert!(invalid.verify().is_err());
+}
+
+fn store_notes(db: &mut db::Db<Blake2b>, sk: &SecretKey, values: &[u64]) -> Vec<NoteVariant> {
+    let pk = sk.public_key();
+
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let note: NoteVariant = if i % 2 == 0 {
+                ObfuscatedNote::output(&pk, *value).0.into()
+            } else {
+                TransparentNote::output(&pk, *value).0.into()
+            };
+
+            let idx = db.store_unspent_note(note).unwrap();
+            db.fetch_note(idx).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn transaction_builder() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let other = SecretKey::default();
+
+    let mut notes = store_notes(&mut db, &sk, &[10, 50, 30, 5]);
+    notes.extend(store_notes(&mut db, &other, &[1000]));
+
+    let mut tx = TransactionBuilder::new(sk)
+        .notes(notes)
+        .output(other.public_key(), 60)
+        .fee(3)
+        .build(&db)
+        .unwrap();
+
+    // 50 + 30 are the fewest notes covering 63, with the smallest change
+    let inputs: Vec<u64> = tx.inputs().iter().map(|i| i.value()).collect();
+    assert_eq!(vec![50, 30], inputs);
+
+    let outputs: Vec<u64> = tx.outputs().iter().map(|o| o.value()).collect();
+    assert_eq!(vec![60, 17], outputs);
+    assert_eq!(3, tx.fee().value());
+
+    tx.prove().unwrap();
+    tx.verify().unwrap();
+}
+
+#[test]
+fn transaction_builder_exact_value() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let notes = store_notes(&mut db, &sk, &[10, 50, 30]);
+
+    let tx = TransactionBuilder::new(sk)
+        .notes(notes)
+        .output(SecretKey::default().public_key(), 27)
+        .fee(3)
+        .build(&db)
+        .unwrap();
+
+    let inputs: Vec<u64> = tx.inputs().iter().map(|i| i.value()).collect();
+    assert_eq!(vec![30], inputs);
+    assert_eq!(1, tx.outputs().len());
+}
+
+#[test]
+fn transaction_builder_undecryptable_note() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+
+    // Owned by the sender, but with a garbage ciphertext
+    let mut note = ObfuscatedNote::output(&pk, 100).0;
+    note.encrypted_value = [0xfau8; 24];
+    let idx = db.store_unspent_note(note.into()).unwrap();
+
+    let mut notes = store_notes(&mut db, &sk, &[30]);
+    notes.push(db.fetch_note(idx).unwrap());
+
+    let tx = TransactionBuilder::new(sk)
+        .notes(notes)
+        .output(SecretKey::default().public_key(), 27)
+        .fee(3)
+        .build(&db)
+        .unwrap();
+
+    let inputs: Vec<u64> = tx.inputs().iter().map(|i| i.value()).collect();
+    assert_eq!(vec![30], inputs);
+}
+
+#[test]
+fn transaction_builder_insufficient_funds() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let notes = store_notes(&mut db, &sk, &[10, 20]);
+
+    let result = TransactionBuilder::new(sk)
+        .notes(notes)
+        .output(SecretKey::default().public_key(), 30)
+        .fee(1)
+        .build(&db);
+
+    match result {
+        Err(Error::InsufficientFunds) => (),
+        r => panic!("Unexpected build result {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn transaction_builder_value_overflow() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let notes = store_notes(&mut db, &sk, &[u64::max_value(), u64::max_value()]);
+
+    let result = TransactionBuilder::new(sk)
+        .notes(notes.clone())
+        .output(SecretKey::default().public_key(), u64::max_value())
+        .fee(1)
+        .build(&db);
+
+    match result {
+        Err(Error::ValueOverflow) => (),
+        r => panic!("Unexpected build result {:?}", r.map(|_| ())),
+    }
+
+    let tx = TransactionBuilder::new(sk)
+        .notes(notes)
+        .output(SecretKey::default().public_key(), u64::max_value() - 1)
+        .fee(1)
+        .build(&db)
+        .unwrap();
+
+    assert_eq!(1, tx.inputs().len());
+    assert_eq!(1, tx.outputs().len());
+}
+
+#[test]
+fn transaction_builder_maximum_notes() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let values = vec![1; MAX_INPUT_NOTES_PER_TRANSACTION + 1];
+    let notes = store_notes(&mut db, &sk, values.as_slice());
+
+    let result = TransactionBuilder::new(sk)
+        .notes(notes)
+        .output(SecretKey::default().public_key(), values.len() as u64)
+        .build(&db);
+
+    match result {
+        Err(Error::MaximumNotes) => (),
+        r => panic!("Unexpected build result {:?}", r.map(|_| ())),
+    }
+
+    let pk = SecretKey::default().public_key();
+    let result = TransactionBuilder::new(sk)
+        .output(pk, 0)
+        .output(pk, 0)
+        .output(pk, 0)
+        .build(&db);
+
+    match result {
+        Err(Error::MaximumNotes) => (),
+        r => panic!("Unexpected build result {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn transaction_verify_batch() {
+    let mut txs = vec![
+        proven_transaction(),
+        proven_transaction(),
+        proven_transaction(),
+        transaction(&[100], &[97], 3),
+    ];
+
+    Transaction::verify_batch(&mut txs[..3]).unwrap();
+
+    // Proof of another transaction
+    let proof = txs[0].proof.clone();
+    txs[2].proof = proof;
+
+    match Transaction::verify_batch(txs.as_mut_slice()) {
+        Err(Error::InvalidBatch(invalid)) => assert_eq!(vec![2, 3], invalid),
+        r => panic!("Unexpected batch verification result {:?}", r),
+    }
+
+    assert!(zk::verify_batch(&[]).is_ok());
+}
@@ -43,17 +43,59 @@ pub const JUBJUB_SCALAR_SERIALIZED_SIZE: usize = 32;
 /// Serialized size of a [`BlsScalar`]
 pub const BLS_SCALAR_SERIALIZED_SIZE: usize = 32;
 
+/// Split `n` bytes from the beginning of the buffer, or fail with [`Error::InvalidLength`] of the
+/// provided field
+pub fn take<'a>(buf: &mut &'a [u8], n: usize, field: &'static str) -> Result<&'a [u8], Error> {
+    if buf.len() < n {
+        return Err(Error::InvalidLength {
+            field,
+            expected: n,
+            actual: buf.len(),
+        });
+    }
+
+    let (bytes, tail) = buf.split_at(n);
+    *buf = tail;
+
+    Ok(bytes)
+}
+
+/// Split `n` mutable bytes from the beginning of the buffer, or fail with
+/// [`Error::InvalidLength`] of the provided field
+pub fn take_mut<'a>(
+    buf: &mut &'a mut [u8],
+    n: usize,
+    field: &'static str,
+) -> Result<&'a mut [u8], Error> {
+    if buf.len() < n {
+        return Err(Error::InvalidLength {
+            field,
+            expected: n,
+            actual: buf.len(),
+        });
+    }
+
+    let (bytes, tail) = mem::take(buf).split_at_mut(n);
+    *buf = tail;
+
+    Ok(bytes)
+}
+
 /// Deserialize a [`JubJubAffine`] from a slice of bytes, and convert it to [`JubJubExtended`]
 pub fn deserialize_compressed_jubjub(bytes: &[u8]) -> Result<JubJubExtended, Error> {
     if bytes.len() < 32 {
-        return Err(Error::InvalidParameters);
+        return Err(Error::InvalidLength {
+            field: "JubJubAffine",
+            expected: 32,
+            actual: bytes.len(),
+        });
     }
 
     let mut array = [0u8; 32];
     array.copy_from_slice(&bytes[..32]);
     let result = JubJubAffine::from_bytes(array);
     if result.is_none().unwrap_u8() == 1 {
-        return Err(Error::InvalidParameters);
+        return Err(Error::InvalidPoint);
     }
 
     Ok(JubJubExtended::from(result.unwrap()))
@@ -62,14 +104,18 @@ pub fn deserialize_compressed_jubjub(bytes: &[u8]) -> Result<JubJubExtended, Err
 /// Deserialize a [`JubJubScalar`] from a slice of bytes
 pub fn deserialize_jubjub_scalar(bytes: &[u8]) -> Result<JubJubScalar, Error> {
     if bytes.len() < 32 {
-        return Err(Error::InvalidParameters);
+        return Err(Error::InvalidLength {
+            field: "JubJubScalar",
+            expected: 32,
+            actual: bytes.len(),
+        });
     }
 
     let mut array = [0u8; 32];
     array.copy_from_slice(&bytes[..32]);
     let result = JubJubScalar::from_bytes(&array);
     if result.is_none().unwrap_u8() == 1 {
-        return Err(Error::InvalidParameters);
+        return Err(Error::InvalidEncoding("JubJubScalar"));
     }
 
     Ok(result.unwrap())
@@ -78,14 +124,18 @@ pub fn deserialize_jubjub_scalar(bytes: &[u8]) -> Result<JubJubScalar, Error> {
 /// Deserialize a [`BlsScalar`] from a slice of bytes
 pub fn deserialize_bls_scalar(bytes: &[u8]) -> Result<BlsScalar, Error> {
     if bytes.len() < 32 {
-        return Err(Error::InvalidParameters);
+        return Err(Error::InvalidLength {
+            field: "BlsScalar",
+            expected: 32,
+            actual: bytes.len(),
+        });
     }
 
     let mut array = [0u8; 32];
     array.copy_from_slice(&bytes[..32]);
     let result = BlsScalar::from_bytes(&array);
     if result.is_none().unwrap_u8() == 1 {
-        return Err(Error::InvalidParameters);
+        return Err(Error::InvalidEncoding("BlsScalar"));
     }
 
     Ok(result.unwrap())
@@ -193,6 +243,14 @@ pub fn bls_scalar_to_bits(scalar: &BlsScalar) -> [u8; 256] {
     res
 }
 
+/// Sum a set of note values, failing with [`Error::ValueOverflow`] if the total doesn't fit a
+/// `u64`
+pub fn checked_sum<I: IntoIterator<Item = u64>>(values: I) -> Result<u64, Error> {
+    values.into_iter().try_fold(0u64, |sum, v| {
+        sum.checked_add(v).ok_or(Error::ValueOverflow)
+    })
+}
+
 /// Generate a [`StdRng`] from a given slice of bytes
 pub fn generate_rng(bytes: &[u8]) -> StdRng {
     let mut hasher = Sha256::default();
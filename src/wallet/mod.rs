@@ -0,0 +1,221 @@
+use crate::{
+    db, utils, Error, Note, NoteVariant, Nullifier, SecretKey, Transaction, TransactionItem,
+    ViewKey,
+};
+
+use kelvin::ByteHash;
+use tracing::warn;
+use unprolix::Getters;
+
+pub use scanner::{ScannedNote, Scanner, DEFAULT_SCAN_BATCH_SIZE};
+
+/// Parallel note scanning for a set of view keys
+pub mod scanner;
+
+#[cfg(test)]
+mod tests;
+
+/// A note found on the storage that is owned by one of the keys of a [`Wallet`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+pub struct OwnedNote {
+    note: NoteVariant,
+    key: usize,
+    value: u64,
+    nullifier: Nullifier,
+    spent: bool,
+}
+
+impl OwnedNote {
+    /// Position of the note on the tree
+    pub fn idx(&self) -> u64 {
+        self.note.idx()
+    }
+}
+
+/// Set of secret keys and the notes they own.
+///
+/// The notes storage is scanned incrementally; every call to [`Wallet::scan`] resumes from the
+/// position where the previous one stopped.
+#[derive(Debug, Default, Clone)]
+pub struct Wallet {
+    keys: Vec<SecretKey>,
+    notes: Vec<OwnedNote>,
+    position: u64,
+}
+
+impl Wallet {
+    /// Create a new wallet with the provided secret keys
+    pub fn new(keys: Vec<SecretKey>) -> Self {
+        Self {
+            keys,
+            notes: vec![],
+            position: 0,
+        }
+    }
+
+    /// Add a secret key to the wallet, and return its index.
+    ///
+    /// The notes already scanned are not checked against the new key, so the scan is restarted
+    /// from the first position.
+    pub fn add_key(&mut self, sk: SecretKey) -> usize {
+        self.keys.push(sk);
+        self.notes.clear();
+        self.position = 0;
+
+        self.keys.len() - 1
+    }
+
+    /// Secret keys of the wallet
+    pub fn keys(&self) -> &[SecretKey] {
+        self.keys.as_slice()
+    }
+
+    /// Next position of the notes storage to be scanned
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// All the scanned notes owned by the wallet, spent or not
+    pub fn notes(&self) -> &[OwnedNote] {
+        self.notes.as_slice()
+    }
+
+    /// Scanned notes owned by the wallet that were not yet spent
+    pub fn unspent_notes(&self) -> impl Iterator<Item = &OwnedNote> {
+        self.notes.iter().filter(|n| !n.spent)
+    }
+
+    /// Scanned unspent notes owned by the secret key with the provided index
+    pub fn unspent_notes_of(&self, key: usize) -> impl Iterator<Item = &OwnedNote> {
+        self.unspent_notes().filter(move |n| n.key == key)
+    }
+
+    /// Confirmed balance of the wallet, as the sum of the values of the unspent notes
+    ///
+    /// Fail with [`Error::ValueOverflow`] if the sum doesn't fit a `u64`
+    pub fn balance(&self) -> Result<u64, Error> {
+        utils::checked_sum(self.unspent_notes().map(|n| n.value))
+    }
+
+    /// Confirmed balance of the secret key with the provided index
+    ///
+    /// Fail with [`Error::ValueOverflow`] if the sum doesn't fit a `u64`
+    pub fn balance_of(&self, key: usize) -> Result<u64, Error> {
+        utils::checked_sum(self.unspent_notes_of(key).map(|n| n.value))
+    }
+
+    /// Scan the notes stored after the last scanned position, and refresh the spent status of the
+    /// owned notes.
+    ///
+    /// An owned note that can't be decrypted is skipped, so a single malformed note doesn't stop
+    /// the scan.
+    ///
+    /// Return the number of new owned notes
+    pub fn scan<H: ByteHash>(&mut self, db: &db::Db<H>) -> Result<usize, Error> {
+        let vks: Vec<ViewKey> = self.keys.iter().map(|sk| sk.view_key()).collect();
+        let mut found = 0;
+
+        loop {
+            let note = match db.fetch_note(self.position) {
+                Ok(note) => note,
+                Err(Error::NotFound) => break,
+                Err(e) => return Err(e),
+            };
+
+            if let Some(key) = vks.iter().position(|vk| note.is_owned_by(vk)) {
+                match note.try_value(&vks[key]) {
+                    Ok(value) => {
+                        self.notes.push(OwnedNote {
+                            note,
+                            key,
+                            value,
+                            nullifier: note.generate_nullifier(&self.keys[key]),
+                            spent: false,
+                        });
+
+                        found += 1;
+                    }
+                    Err(e) => warn!("Skipping the undecryptable note {}: {}", self.position, e),
+                }
+            }
+
+            self.position += 1;
+        }
+
+        self.refresh_spent(db)?;
+
+        Ok(found)
+    }
+
+    /// Check the nullifiers of the unspent notes against the storage, and flag the notes that
+    /// were spent
+    pub fn refresh_spent<H: ByteHash>(&mut self, db: &db::Db<H>) -> Result<(), Error> {
+        self.notes
+            .iter_mut()
+            .filter(|n| !n.spent)
+            .try_for_each(|n| {
+                n.spent = db.fetch_nullifier(&n.nullifier)?.is_some();
+
+                Ok(())
+            })
+    }
+}
+
+/// Unconfirmed balance of the secret key, considering the pending transactions of the mempool.
+///
+/// The balance is the sum of the values of the confirmed notes owned by the key, minus the notes
+/// whose nullifiers are stored or spent by the inputs of the pending transactions, plus the owned
+/// outputs of the pending transactions. The fees are not considered, since their owner is defined
+/// only when the transaction is included in a block.
+///
+/// The inputs are matched via their nullifiers, so the inputs that had their sensitive info
+/// cleared, such as the ones received via RPC, are accounted too.
+///
+/// Fail with [`Error::ValueOverflow`] if the sum doesn't fit a `u64`
+pub fn mempool_balance<H: ByteHash>(
+    sk: &SecretKey,
+    db: &db::Db<H>,
+    pending: &[Transaction],
+) -> Result<u64, Error> {
+    let vk = sk.view_key();
+
+    let spent: Vec<Nullifier> = pending
+        .iter()
+        .flat_map(|tx| tx.inputs().iter())
+        .map(|i| *i.nullifier())
+        .collect();
+
+    let mut confirmed = vec![];
+    for note in Scanner::new(vec![vk]).scan(db, 0..db.notes_count(), |_, _| ())? {
+        let nullifier = note.note().generate_nullifier(sk);
+
+        if !spent.contains(&nullifier) && db.fetch_nullifier(&nullifier)?.is_none() {
+            confirmed.push(*note.value());
+        }
+    }
+
+    utils::checked_sum(confirmed)?
+        .checked_add(pending_balance(&vk, pending)?)
+        .ok_or(Error::ValueOverflow)
+}
+
+/// Sum of the values of the outputs of the pending transactions owned by the view key.
+///
+/// An owned output that can't be decrypted is skipped, as in [`Scanner::scan`]. Fail with
+/// [`Error::ValueOverflow`] if the sum doesn't fit a `u64`
+pub fn pending_balance(vk: &ViewKey, pending: &[Transaction]) -> Result<u64, Error> {
+    let values = pending
+        .iter()
+        .flat_map(|tx| tx.outputs().iter())
+        .map(|o| o.note())
+        .filter(|n| n.is_owned_by(vk))
+        .filter_map(|n| match n.try_value(vk) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("Skipping the undecryptable pending output: {}", e);
+                None
+            }
+        });
+
+    utils::checked_sum(values)
+}
@@ -0,0 +1,189 @@
+use crate::{db, Error, JubJubScalar, Note, NoteVariant, ViewKey};
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use kelvin::ByteHash;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tracing::warn;
+use unprolix::Getters;
+
+/// Default number of notes fetched from the storage before they are checked in parallel
+pub const DEFAULT_SCAN_BATCH_SIZE: usize = 4096;
+
+/// A note found by a [`Scanner`], decrypted with the view key that owns it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+pub struct ScannedNote {
+    note: NoteVariant,
+    key: usize,
+    value: u64,
+    blinding_factor: JubJubScalar,
+}
+
+impl ScannedNote {
+    /// Position of the note on the tree
+    pub fn idx(&self) -> u64 {
+        self.note.idx()
+    }
+}
+
+/// Parallel scanner of the notes owned by a set of view keys.
+///
+/// The notes are fetched from the storage in batches, and the ownership checks and decryption of
+/// every batch are split across a thread pool. The pool is built once with the scanner, and shared
+/// by its clones.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    vks: Vec<ViewKey>,
+    pool: Option<Arc<ThreadPool>>,
+    batch_size: usize,
+}
+
+impl Scanner {
+    /// Create a new scanner for the provided view keys
+    pub fn new(vks: Vec<ViewKey>) -> Self {
+        Self {
+            vks,
+            pool: None,
+            batch_size: DEFAULT_SCAN_BATCH_SIZE,
+        }
+    }
+
+    /// Scan with a dedicated pool of the provided number of threads. If zero, the global rayon
+    /// pool is used, as by default
+    pub fn threads(mut self, threads: usize) -> Result<Self, Error> {
+        self.pool = match threads {
+            0 => None,
+            _ => Some(Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(Error::other)?,
+            )),
+        };
+
+        Ok(self)
+    }
+
+    /// Set the number of notes checked in parallel between two progress reports
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// View keys of the scanner
+    pub fn keys(&self) -> &[ViewKey] {
+        self.vks.as_slice()
+    }
+
+    /// Scan the notes of the provided position range, and return the owned notes ordered by their
+    /// positions.
+    ///
+    /// The range is clamped to the stored notes. After every batch, `progress` is called with the
+    /// number of scanned notes and the total number of notes of the range.
+    ///
+    /// An owned note that can't be decrypted is skipped, so a single malformed note doesn't stop
+    /// the scan.
+    pub fn scan<H, F>(
+        &self,
+        db: &db::Db<H>,
+        range: Range<u64>,
+        mut progress: F,
+    ) -> Result<Vec<ScannedNote>, Error>
+    where
+        H: ByteHash,
+        F: FnMut(u64, u64),
+    {
+        let end = range.end.min(db.notes_count());
+        let start = range.start.min(end);
+        let total = end - start;
+
+        let mut owned = vec![];
+        let mut position = start;
+
+        while position < end {
+            let batch_end = end.min(position + self.batch_size as u64);
+            let batch = db
+                .notes_range(position..batch_end)
+                .collect::<Result<Vec<NoteVariant>, Error>>()?;
+
+            let scan_batch = || -> Vec<ScannedNote> {
+                batch
+                    .par_iter()
+                    .filter_map(|note| self.scan_note(note))
+                    .collect()
+            };
+
+            let found = match &self.pool {
+                Some(pool) => pool.install(scan_batch),
+                None => scan_batch(),
+            };
+
+            owned.extend(found);
+
+            position = batch_end;
+            progress(position - start, total);
+        }
+
+        Ok(owned)
+    }
+
+    /// Check if any note of the provided position range is owned by any of the view keys.
+    ///
+    /// The notes are checked in batches as in [`Scanner::scan`], and the check stops at the batch
+    /// of the first owned note. The notes are not decrypted.
+    pub fn owns_any<H: ByteHash>(&self, db: &db::Db<H>, range: Range<u64>) -> Result<bool, Error> {
+        let end = range.end.min(db.notes_count());
+        let mut position = range.start.min(end);
+
+        while position < end {
+            let batch_end = end.min(position + self.batch_size as u64);
+            let batch = db
+                .notes_range(position..batch_end)
+                .collect::<Result<Vec<NoteVariant>, Error>>()?;
+
+            let check_batch = || {
+                batch
+                    .par_iter()
+                    .any(|note| self.vks.iter().any(|vk| note.is_owned_by(vk)))
+            };
+
+            let owned = match &self.pool {
+                Some(pool) => pool.install(check_batch),
+                None => check_batch(),
+            };
+
+            if owned {
+                return Ok(true);
+            }
+
+            position = batch_end;
+        }
+
+        Ok(false)
+    }
+
+    /// Check the note against all the view keys, and decrypt it with the first that owns it
+    fn scan_note(&self, note: &NoteVariant) -> Option<ScannedNote> {
+        let key = self.vks.iter().position(|vk| note.is_owned_by(vk))?;
+        let vk = &self.vks[key];
+
+        let decrypted = note
+            .try_value(vk)
+            .and_then(|value| Ok((value, note.try_blinding_factor(vk)?)));
+
+        match decrypted {
+            Ok((value, blinding_factor)) => Some(ScannedNote {
+                note: *note,
+                key,
+                value,
+                blinding_factor,
+            }),
+            Err(e) => {
+                warn!("Skipping the undecryptable note {}: {}", note.idx(), e);
+                None
+            }
+        }
+    }
+}
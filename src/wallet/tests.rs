@@ -0,0 +1,296 @@
+use super::{mempool_balance, pending_balance, Scanner, Wallet};
+use crate::{
+    db, Error, MerkleProofProvider, Note, NoteGenerator, NoteVariant, ObfuscatedNote, PublicKey,
+    SecretKey, Transaction, TransactionItem, TransparentNote,
+};
+
+use kelvin::Blake2b;
+
+fn store_note(db: &mut db::Db<Blake2b>, pk: &PublicKey, value: u64, obfuscated: bool) -> u64 {
+    let note: NoteVariant = if obfuscated {
+        ObfuscatedNote::output(pk, value).0.into()
+    } else {
+        TransparentNote::output(pk, value).0.into()
+    };
+
+    db.store_unspent_note(note).unwrap()
+}
+
+fn spend_note(db: &mut db::Db<Blake2b>, sk: SecretKey, idx: u64) {
+    let note = db.fetch_note(idx).unwrap();
+    let merkle_opening = db.opening(&note).unwrap();
+
+    let mut tx = Transaction::default();
+    tx.push_input(note.to_transaction_input(merkle_opening, sk).unwrap())
+        .unwrap();
+
+    db.store_transaction(&tx).unwrap();
+}
+
+#[test]
+fn wallet_scan() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk_a = SecretKey::default();
+    let sk_b = SecretKey::default();
+    let other = SecretKey::default();
+
+    store_note(&mut db, &sk_a.public_key(), 10, false);
+    store_note(&mut db, &other.public_key(), 100, true);
+    store_note(&mut db, &sk_b.public_key(), 20, true);
+    store_note(&mut db, &sk_a.public_key(), 30, true);
+
+    let mut wallet = Wallet::new(vec![sk_a, sk_b]);
+
+    assert_eq!(3, wallet.scan(&db).unwrap());
+    assert_eq!(4, wallet.position());
+    assert_eq!(60, wallet.balance().unwrap());
+    assert_eq!(40, wallet.balance_of(0).unwrap());
+    assert_eq!(20, wallet.balance_of(1).unwrap());
+
+    // Nothing new to scan
+    assert_eq!(0, wallet.scan(&db).unwrap());
+    assert_eq!(60, wallet.balance().unwrap());
+
+    store_note(&mut db, &other.public_key(), 100, false);
+    store_note(&mut db, &sk_b.public_key(), 5, false);
+
+    assert_eq!(1, wallet.scan(&db).unwrap());
+    assert_eq!(6, wallet.position());
+    assert_eq!(65, wallet.balance().unwrap());
+    assert_eq!(25, wallet.balance_of(1).unwrap());
+}
+
+#[test]
+fn wallet_spent_notes() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+
+    let spent = store_note(&mut db, &pk, 10, true);
+    store_note(&mut db, &pk, 15, false);
+
+    let mut wallet = Wallet::new(vec![sk]);
+    wallet.scan(&db).unwrap();
+    assert_eq!(25, wallet.balance().unwrap());
+
+    spend_note(&mut db, sk, spent);
+
+    // The fee note of the spending transaction is not owned by the wallet
+    assert_eq!(0, wallet.scan(&db).unwrap());
+    assert_eq!(15, wallet.balance().unwrap());
+    assert_eq!(2, wallet.notes().len());
+    assert_eq!(1, wallet.unspent_notes().count());
+    assert!(wallet
+        .notes()
+        .iter()
+        .any(|n| n.idx() == spent && *n.spent()));
+}
+
+#[test]
+fn wallet_add_key_rescans() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk_a = SecretKey::default();
+    let sk_b = SecretKey::default();
+
+    store_note(&mut db, &sk_a.public_key(), 10, true);
+    store_note(&mut db, &sk_b.public_key(), 20, true);
+
+    let mut wallet = Wallet::new(vec![sk_a]);
+    wallet.scan(&db).unwrap();
+    assert_eq!(10, wallet.balance().unwrap());
+
+    assert_eq!(1, wallet.add_key(sk_b));
+    assert_eq!(0, wallet.position());
+
+    assert_eq!(2, wallet.scan(&db).unwrap());
+    assert_eq!(30, wallet.balance().unwrap());
+}
+
+#[test]
+fn wallet_undecryptable_note() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+
+    store_note(&mut db, &pk, 10, true);
+
+    // Owned by the key, but with a garbage ciphertext
+    let mut note = ObfuscatedNote::output(&pk, 20).0;
+    note.encrypted_value = [0xfau8; 24];
+    db.store_unspent_note(note.into()).unwrap();
+
+    store_note(&mut db, &pk, 5, true);
+
+    let mut wallet = Wallet::new(vec![sk]);
+    assert_eq!(2, wallet.scan(&db).unwrap());
+    assert_eq!(3, wallet.position());
+    assert_eq!(15, wallet.balance().unwrap());
+
+    let notes = Scanner::new(vec![sk.view_key()])
+        .scan(&db, 0..3, |_, _| ())
+        .unwrap();
+    let values: Vec<u64> = notes.iter().map(|n| *n.value()).collect();
+    assert_eq!(vec![10, 5], values);
+}
+
+#[test]
+fn wallet_balance_overflow() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+
+    store_note(&mut db, &sk.public_key(), u64::max_value(), false);
+    store_note(&mut db, &sk.public_key(), 1, true);
+
+    let mut wallet = Wallet::new(vec![sk]);
+    wallet.scan(&db).unwrap();
+
+    match wallet.balance() {
+        Err(Error::ValueOverflow) => (),
+        r => panic!("Unexpected balance {:?}", r),
+    }
+}
+
+#[test]
+fn scanner_parallel() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk_a = SecretKey::default();
+    let sk_b = SecretKey::default();
+    let other = SecretKey::default();
+
+    let mut expected = vec![];
+    for i in 0..40u64 {
+        let (key, pk) = match i % 3 {
+            0 => (Some(0), sk_a.public_key()),
+            1 => (Some(1), sk_b.public_key()),
+            _ => (None, other.public_key()),
+        };
+
+        let idx = store_note(&mut db, &pk, i, i % 2 == 0);
+        if let Some(key) = key {
+            expected.push((idx, key, i));
+        }
+    }
+
+    let scanner = Scanner::new(vec![sk_a.view_key(), sk_b.view_key()])
+        .threads(4)
+        .unwrap()
+        .batch_size(7);
+
+    let mut reports = vec![];
+    let notes = scanner
+        .scan(&db, 0..100, |scanned, total| reports.push((scanned, total)))
+        .unwrap();
+
+    let found: Vec<(u64, usize, u64)> = notes
+        .iter()
+        .map(|n| (n.idx(), *n.key(), *n.value()))
+        .collect();
+    assert_eq!(expected, found);
+
+    assert!(scanner.owns_any(&db, 0..100).unwrap());
+    assert!(!scanner.owns_any(&db, 0..0).unwrap());
+    let stranger = Scanner::new(vec![SecretKey::default().view_key()]);
+    assert!(!stranger.owns_any(&db, 0..100).unwrap());
+
+    let vk = sk_a.view_key();
+    notes.iter().filter(|n| *n.key() == 0).for_each(|n| {
+        let blinding_factor = n.note().try_blinding_factor(&vk).unwrap();
+        assert_eq!(blinding_factor, *n.blinding_factor());
+    });
+
+    assert_eq!(Some(&(40, 40)), reports.last());
+    assert_eq!(6, reports.len());
+    assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+
+    let notes = scanner.scan(&db, 10..20, |_, _| ()).unwrap();
+    assert!(notes.iter().all(|n| n.idx() >= 10 && n.idx() < 20));
+    let in_range = expected
+        .iter()
+        .filter(|(idx, _, _)| *idx >= 10 && *idx < 20)
+        .count();
+    assert_eq!(in_range, notes.len());
+
+    assert!(scanner.scan(&db, 50..60, |_, _| ()).unwrap().is_empty());
+}
+
+#[test]
+fn wallet_mempool_balance() {
+    let mut db = db::Db::<Blake2b>::default();
+
+    let sk = SecretKey::default();
+    let pk = sk.public_key();
+    let vk = sk.view_key();
+    let other = SecretKey::default().public_key();
+
+    let spent = store_note(&mut db, &pk, 10, false);
+    let cleared = store_note(&mut db, &pk, 30, true);
+    store_note(&mut db, &other, 100, true);
+
+    // The notes spent on the db are not accounted
+    let stored = store_note(&mut db, &pk, 5, true);
+    spend_note(&mut db, sk, stored);
+
+    assert_eq!(40, mempool_balance(&sk, &db, &[]).unwrap());
+
+    let note = db.fetch_note(spent).unwrap();
+    let merkle_opening = db.opening(&note).unwrap();
+    let mut pending = Transaction::default();
+    pending
+        .push_input(note.to_transaction_input(merkle_opening, sk).unwrap())
+        .unwrap();
+    let (note, blinding_factor) = ObfuscatedNote::output(&pk, 7);
+    pending
+        .push_output(note.to_transaction_output(7, blinding_factor, pk))
+        .unwrap();
+    let (note, blinding_factor) = TransparentNote::output(&other, 2);
+    pending
+        .push_output(note.to_transaction_output(2, blinding_factor, other))
+        .unwrap();
+
+    // Both pending transactions spend the same note
+    let txs = vec![pending.clone(), pending];
+    let balance = mempool_balance(&sk, &db, txs.as_slice()).unwrap();
+    assert_eq!(40 - 10 + 7 + 7, balance);
+    assert_eq!(7 + 7, pending_balance(&vk, txs.as_slice()).unwrap());
+
+    // A cleared input is matched via its nullifier
+    let note = db.fetch_note(cleared).unwrap();
+    let merkle_opening = db.opening(&note).unwrap();
+    let mut pending = Transaction::default();
+    pending
+        .push_input(note.to_transaction_input(merkle_opening, sk).unwrap())
+        .unwrap();
+    pending.clear_sensitive_info();
+    assert_eq!(40 - 30, mempool_balance(&sk, &db, &[pending]).unwrap());
+
+    // An owned output with a garbage ciphertext is skipped
+    let (mut note, blinding_factor) = ObfuscatedNote::output(&pk, 9);
+    note.encrypted_value = [0xfau8; 24];
+    let mut pending = Transaction::default();
+    pending
+        .push_output(note.to_transaction_output(9, blinding_factor, pk))
+        .unwrap();
+    let (note, blinding_factor) = ObfuscatedNote::output(&pk, 3);
+    pending
+        .push_output(note.to_transaction_output(3, blinding_factor, pk))
+        .unwrap();
+    let txs = vec![pending];
+    assert_eq!(3, pending_balance(&vk, txs.as_slice()).unwrap());
+    assert_eq!(40 + 3, mempool_balance(&sk, &db, txs.as_slice()).unwrap());
+
+    let (note, blinding_factor) = ObfuscatedNote::output(&pk, u64::MAX);
+    let mut pending = Transaction::default();
+    pending
+        .push_output(note.to_transaction_output(u64::MAX, blinding_factor, pk))
+        .unwrap();
+    match mempool_balance(&sk, &db, &[pending]) {
+        Err(Error::ValueOverflow) => (),
+        r => panic!("Unexpected balance {:?}", r),
+    }
+}
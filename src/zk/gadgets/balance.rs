@@ -1,46 +1,43 @@
-use crate::{BlsScalar, Transaction, TransactionItem};
+use crate::BlsScalar;
 
-use dusk_plonk::constraint_system::StandardComposer;
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
 
 /// Prove that the amount inputted equals the amount outputted
-pub fn balance(composer: &mut StandardComposer, tx: &Transaction) {
+///
+/// The values are expected to be the ones allocated by the [`super::commitment`] gadget, so the
+/// balance holds for the committed values.
+pub fn balance(
+    composer: &mut StandardComposer,
+    inputs: &[Variable],
+    outputs: &[Variable],
+    fee: Variable,
+) {
     let mut sum = composer.zero_var;
-    for item in tx.inputs().iter() {
-        let value = composer.add_input(BlsScalar::from(item.value()));
+    for value in inputs.iter() {
         sum = composer.add(
             (BlsScalar::one(), sum),
-            (BlsScalar::one(), value),
+            (BlsScalar::one(), *value),
             BlsScalar::zero(),
             BlsScalar::zero(),
         );
     }
 
-    for item in tx.outputs().iter() {
-        let value = composer.add_input(BlsScalar::from(item.value()));
+    for value in outputs.iter().chain(Some(&fee)) {
         sum = composer.add(
             (BlsScalar::one(), sum),
-            (-BlsScalar::one(), value),
+            (-BlsScalar::one(), *value),
             BlsScalar::zero(),
             BlsScalar::zero(),
         );
     }
 
-    let fee = *tx.fee();
-
-    let value = composer.add_input(BlsScalar::from(fee.value()));
-    sum = composer.add(
-        (BlsScalar::one(), sum),
-        (-BlsScalar::one(), value),
-        BlsScalar::zero(),
-        BlsScalar::zero(),
-    );
-
     composer.constrain_to_constant(sum, BlsScalar::zero(), BlsScalar::zero());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::zk::gadgets;
     use crate::{crypto, Note, NoteGenerator, SecretKey, Transaction, TransparentNote};
     use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
     use dusk_plonk::fft::EvaluationDomain;
@@ -80,7 +77,19 @@ mod tests {
 
         let mut composer = StandardComposer::new();
 
-        balance(&mut composer, &tx);
+        let inputs: Vec<Variable> = tx
+            .inputs()
+            .iter()
+            .map(|i| gadgets::commitment(&mut composer, i).value)
+            .collect();
+        let outputs: Vec<Variable> = tx
+            .circuit_outputs()
+            .iter()
+            .map(|o| gadgets::commitment(&mut composer, o).value)
+            .collect();
+        let fee = gadgets::commitment(&mut composer, tx.fee()).value;
+
+        balance(&mut composer, &inputs, &outputs, fee);
 
         composer.add_dummy_constraints();
 
@@ -135,7 +144,19 @@ mod tests {
 
         let mut composer = StandardComposer::new();
 
-        balance(&mut composer, &tx);
+        let inputs: Vec<Variable> = tx
+            .inputs()
+            .iter()
+            .map(|i| gadgets::commitment(&mut composer, i).value)
+            .collect();
+        let outputs: Vec<Variable> = tx
+            .circuit_outputs()
+            .iter()
+            .map(|o| gadgets::commitment(&mut composer, o).value)
+            .collect();
+        let fee = gadgets::commitment(&mut composer, tx.fee()).value;
+
+        balance(&mut composer, &inputs, &outputs, fee);
 
         composer.add_dummy_constraints();
 
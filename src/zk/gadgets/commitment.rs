@@ -1,35 +1,179 @@
-use crate::{NoteVariant, TransactionItem, ViewKey};
-
-use dusk_plonk::constraint_system::StandardComposer;
-
-/// Prove knowledge of the value and blinding factor, which make up the value commitment.
-pub fn commitment(composer: &mut StandardComposer, note: &NoteVariant, vk: Option<&ViewKey>) {
-    // let value = composer.add_input(BlsScalar::from(note.value(vk)));
-    // let blinding_factor = composer.add_input(note.blinding_factor(vk));
-
-    // let output = sponge_hash_gadget(composer, &[value, blinding_factor]);
-
-    // let commitment = composer.add_input(*note.value_commitment());
-    // composer.add_gate(
-    //     output,
-    //     commitment,
-    //     composer.zero_var,
-    //     -BlsScalar::one(),
-    //     BlsScalar::one(),
-    //     BlsScalar::one(),
-    //     BlsScalar::zero(),
-    //     BlsScalar::zero(),
-    // );
-    //
-    // NOTE:
-    // We are currently mocking the commitment gadget, as we are still missing the ECC gate
-    // implementation.
+use super::constrain_equal;
+use crate::{note, BlsScalar, JubJubAffine, JubJubExtended, TransactionItem};
+
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
+use jubjub::GENERATOR;
+
+/// Variables of a value commitment allocated on the circuit
+#[derive(Debug, Clone, Copy)]
+pub struct ValueCommitment {
+    /// Committed value, decomposed in 64 bits
+    pub value: Variable,
+    /// Affine `x` of the commitment point
+    pub x: Variable,
+    /// Affine `y` of the commitment point
+    pub y: Variable,
+}
+
+/// Point of the embedded curve allocated on the circuit, along with its witness
+pub(super) struct Point {
+    native: JubJubExtended,
+    pub x: Variable,
+    pub y: Variable,
+}
+
+/// Prove knowledge of the value and blinding factor, which make up the value commitment
+/// `v · G + b · H` of the item.
+///
+/// Return the variables of the value and the commitment point, so the remaining gadgets can
+/// reuse them instead of allocating unconstrained copies.
+pub fn commitment<T: TransactionItem>(
+    composer: &mut StandardComposer,
+    item: &T,
+) -> ValueCommitment {
+    let value = composer.add_input(BlsScalar::from(item.value()));
+
+    let value_bits = bits(composer, &item.value().to_le_bytes());
+    let sum = bits_sum(composer, &value_bits);
+    constrain_equal(composer, sum, value);
+
+    let blinding_factor_bits = bits(composer, &item.blinding_factor().to_bytes());
+
+    let vg = fixed_base_mul(composer, JubJubExtended::from(GENERATOR), &value_bits);
+    let bh = fixed_base_mul(
+        composer,
+        note::value_commitment_generator(),
+        &blinding_factor_bits,
+    );
+    let commitment = point_add(composer, &vg, &bh);
+
+    ValueCommitment {
+        value,
+        x: commitment.x,
+        y: commitment.y,
+    }
+}
+
+/// Allocate the little-endian bits of the provided bytes as boolean variables
+pub(super) fn bits(composer: &mut StandardComposer, bytes: &[u8]) -> Vec<(bool, Variable)> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .map(|bit| {
+            let var = composer.add_input(BlsScalar::from(bit as u64));
+            composer.boolean_gate(var);
+
+            (bit, var)
+        })
+        .collect()
+}
+
+/// Compose the little-endian bits back into the scalar `Σ bᵢ · 2ⁱ`
+pub(super) fn bits_sum(composer: &mut StandardComposer, bits: &[(bool, Variable)]) -> Variable {
+    let mut sum = composer.zero_var;
+    let mut coeff = BlsScalar::one();
+    for (_, bit) in bits.iter() {
+        sum = composer.add(
+            (BlsScalar::one(), sum),
+            (coeff, *bit),
+            BlsScalar::zero(),
+            BlsScalar::zero(),
+        );
+        coeff = coeff + coeff;
+    }
+
+    sum
+}
+
+/// Compute `Σ bᵢ · 2ⁱ · base` via double-and-add over constant bases, so the circuit
+/// description doesn't depend on the scalar
+pub(super) fn fixed_base_mul(
+    composer: &mut StandardComposer,
+    base: JubJubExtended,
+    bits: &[(bool, Variable)],
+) -> Point {
+    let mut base = base;
+    let mut acc: Option<Point> = None;
+
+    for (bit, var) in bits.iter() {
+        let affine = JubJubAffine::from(base);
+
+        // Select either `2ⁱ · base` or the identity `(0, 1)`
+        let x = composer.add(
+            (affine.get_x(), *var),
+            (BlsScalar::zero(), composer.zero_var),
+            BlsScalar::zero(),
+            BlsScalar::zero(),
+        );
+        let y = composer.add(
+            (affine.get_y() - BlsScalar::one(), *var),
+            (BlsScalar::zero(), composer.zero_var),
+            BlsScalar::one(),
+            BlsScalar::zero(),
+        );
+        let native = if *bit {
+            base
+        } else {
+            JubJubExtended::identity()
+        };
+        let selected = Point { native, x, y };
+
+        acc = Some(match acc {
+            Some(acc) => point_add(composer, &acc, &selected),
+            None => selected,
+        });
+
+        base = base.double();
+    }
+
+    acc.expect("The scalar bits are never empty")
+}
+
+/// Add two points with the twisted Edwards unified addition law, for `a = -1`
+///
+/// `x₃ · (1 + d·x₁x₂y₁y₂) = x₁y₂ + y₁x₂`
+/// `y₃ · (1 - d·x₁x₂y₁y₂) = y₁y₂ + x₁x₂`
+fn point_add(composer: &mut StandardComposer, p: &Point, q: &Point) -> Point {
+    let native = p.native + q.native;
+    let affine = JubJubAffine::from(native);
+    let x = composer.add_input(affine.get_x());
+    let y = composer.add_input(affine.get_y());
+
+    let one = BlsScalar::one();
+    let zero = BlsScalar::zero();
+
+    let x1y2 = composer.mul(one, p.x, q.y, zero, zero);
+    let y1x2 = composer.mul(one, p.y, q.x, zero, zero);
+    let y1y2 = composer.mul(one, p.y, q.y, zero, zero);
+    let x1x2 = composer.mul(one, p.x, q.x, zero, zero);
+    let t = composer.mul(edwards_d(), x1x2, y1y2, zero, zero);
+
+    let num = composer.add((one, x1y2), (one, y1x2), zero, zero);
+    let den = composer.add((one, t), (zero, composer.zero_var), one, zero);
+    let prod = composer.mul(one, x, den, zero, zero);
+    constrain_equal(composer, prod, num);
+
+    let num = composer.add((one, y1y2), (one, x1x2), zero, zero);
+    let den = composer.add((-one, t), (zero, composer.zero_var), one, zero);
+    let prod = composer.mul(one, y, den, zero, zero);
+    constrain_equal(composer, prod, num);
+
+    Point { native, x, y }
+}
+
+/// Curve parameter `d` of JubJub, recovered from the generator as
+/// `d = (y² - x² - 1) / (x² · y²)`
+fn edwards_d() -> BlsScalar {
+    let x2 = GENERATOR.get_x().square();
+    let y2 = GENERATOR.get_y().square();
+
+    (y2 - x2 - BlsScalar::one()) * (x2 * y2).invert().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{crypto, Note, NoteGenerator, SecretKey, TransparentNote};
+    use crate::{crypto, zk, Note, NoteGenerator, SecretKey, TransparentNote};
     use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
     use dusk_plonk::fft::EvaluationDomain;
     use merlin::Transcript;
@@ -37,7 +181,6 @@ mod tests {
     #[test]
     fn commitment_gadget() {
         let sk = SecretKey::default();
-        let vk = sk.view_key();
         let pk = sk.public_key();
         let value = 100;
         let note = TransparentNote::output(&pk, value).0;
@@ -46,13 +189,10 @@ mod tests {
 
         let mut composer = StandardComposer::new();
 
-        commitment(&mut composer, input.note(), Some(&vk));
-        composer.add_dummy_constraints();
-        // NOTE: this is here to make the test pass, as one set of dummy constraints
-        // isn't enough when no extra gates are added. It should be removed once the
-        // commitment gadget is properly implemented.
+        let pi = zk::public_input(&mut composer, note.value_commitment().get_x());
+        let c = commitment(&mut composer, &input);
+        constrain_equal(&mut composer, c.x, pi);
         composer.add_dummy_constraints();
-        ////////////////////////////////////////////////////////////////////////////
 
         // Generate Composer & Public Parameters
         let pub_params = PublicParameters::setup(1 << 17, &mut rand::thread_rng()).unwrap();
@@ -69,4 +209,37 @@ mod tests {
 
         assert!(proof.verify(&circuit, &mut transcript, &vk, &composer.public_inputs()));
     }
+
+    #[test]
+    #[ignore]
+    fn commitment_invalid() {
+        let sk = SecretKey::default();
+        let pk = sk.public_key();
+        let value = 100;
+        let (note, blinding_factor) = TransparentNote::output(&pk, value);
+        // Claim a value the note doesn't commit to
+        let output = note.to_transaction_output(value + 1, blinding_factor, pk);
+
+        let mut composer = StandardComposer::new();
+
+        let pi = zk::public_input(&mut composer, note.value_commitment().get_x());
+        let c = commitment(&mut composer, &output);
+        constrain_equal(&mut composer, c.x, pi);
+        composer.add_dummy_constraints();
+
+        // Generate Composer & Public Parameters
+        let pub_params = PublicParameters::setup(1 << 17, &mut rand::thread_rng()).unwrap();
+        let (ck, vk) = pub_params.trim(1 << 16).unwrap();
+        let mut transcript = Transcript::new(b"TEST");
+
+        let circuit = composer.preprocess(
+            &ck,
+            &mut transcript,
+            &EvaluationDomain::new(composer.circuit_size()).unwrap(),
+        );
+
+        let proof = composer.prove(&ck, &circuit, &mut transcript.clone());
+
+        assert!(!proof.verify(&circuit, &mut transcript, &vk, &composer.public_inputs()));
+    }
 }
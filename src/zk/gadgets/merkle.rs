@@ -1,55 +1,116 @@
-use crate::{Note, TransactionInput, TransactionItem};
+use super::constrain_equal;
+use crate::{BlsScalar, TransactionInput};
 
-use dusk_plonk::constraint_system::StandardComposer;
-use poseidon252::merkle_proof::{merkle_opening_gadget, PoseidonBranch};
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
+use hades252::strategies::{GadgetStrategy, Strategy};
 
-/// Verify the merkle opening
-pub fn merkle(composer: &mut StandardComposer, branch: PoseidonBranch, input: &TransactionInput) {
-    let leaf = composer.add_input(input.note().hash());
-    let root = branch.root;
-    merkle_opening_gadget(composer, branch, leaf, root);
+/// Verify the merkle opening of the input note
+///
+/// The leaf is provided by the pre-image of the note, and the root is expected to be a public
+/// input of the circuit
+pub fn merkle(
+    composer: &mut StandardComposer,
+    input: &TransactionInput,
+    note_hash: Variable,
+    root: Variable,
+) {
+    let levels = input.merkle_opening.levels();
+    let mut node = note_hash;
+
+    for (i, level) in levels.iter().enumerate() {
+        let mut perm = [composer.zero_var; hades252::WIDTH];
+        perm.iter_mut()
+            .zip(level.data().iter())
+            .for_each(|(p, d)| *p = composer.add_input(*d));
+
+        let selected = select_leaf(composer, level.idx(), &perm[1..]);
+        constrain_equal(composer, selected, node);
+
+        // The last level contains only the root
+        if i == levels.len() - 1 {
+            constrain_equal(composer, selected, root);
+        } else {
+            node = GadgetStrategy::new(composer).poseidon(&mut perm);
+        }
+    }
+}
+
+/// Select the leaf of the provided position via a set of boolean flags, so the circuit
+/// description doesn't depend on the position of the note
+fn select_leaf(composer: &mut StandardComposer, idx: usize, leaves: &[Variable]) -> Variable {
+    let mut flags = composer.zero_var;
+    let mut selected = composer.zero_var;
+
+    leaves.iter().enumerate().for_each(|(i, leaf)| {
+        let flag = composer.add_input(BlsScalar::from((i == idx) as u64));
+        composer.boolean_gate(flag);
+
+        let leaf = composer.mul(
+            BlsScalar::one(),
+            flag,
+            *leaf,
+            BlsScalar::zero(),
+            BlsScalar::zero(),
+        );
+
+        selected = composer.add(
+            (BlsScalar::one(), selected),
+            (BlsScalar::one(), leaf),
+            BlsScalar::zero(),
+            BlsScalar::zero(),
+        );
+
+        flags = composer.add(
+            (BlsScalar::one(), flags),
+            (BlsScalar::one(), flag),
+            BlsScalar::zero(),
+            BlsScalar::zero(),
+        );
+    });
+
+    composer.constrain_to_constant(flags, BlsScalar::one(), BlsScalar::zero());
+
+    selected
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{crypto, BlsScalar, NoteGenerator, SecretKey, TransparentNote};
+    use crate::{
+        db, zk, MerkleProofProvider, Note, NoteGenerator, NoteVariant, SecretKey, TransactionItem,
+        TransparentNote,
+    };
     use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
     use dusk_plonk::fft::EvaluationDomain;
     use kelvin::Blake2b;
     use merlin::Transcript;
-    use poseidon252::PoseidonTree;
-    use poseidon252::StorageScalar;
-    use rand::Rng;
 
     #[test]
     fn merkle_gadget() {
+        let mut db = db::Db::<Blake2b>::default();
+
+        // Generate a tree with random notes inside.
+        // However, we set our note on a specific index.
         let sk = SecretKey::default();
         let pk = sk.public_key();
         let value = 100;
-        let note = TransparentNote::output(&pk, value).0;
-        let merkle_opening = crypto::MerkleProof::mock(note.hash());
-        let input = note.to_transaction_input(merkle_opening, sk).unwrap();
-
-        // Generate a tree with random scalars inside.
-        // However, we set our nullifier on a specific index.
-        let index = rand::thread_rng().gen_range(0, 1024);
-        let mut ptree: PoseidonTree<_, Blake2b> = PoseidonTree::new(17);
-        for i in 0..1024u64 {
+        let index = 6;
+        let mut note = NoteVariant::default();
+        for i in 0..16 {
+            let n: NoteVariant = TransparentNote::output(&pk, value).0.into();
+            let idx = db.store_unspent_note(n).unwrap();
             if i == index {
-                ptree.push(StorageScalar(input.note().hash())).unwrap();
-                continue;
+                note = db.fetch_note(idx).unwrap();
             }
-            ptree
-                .push(StorageScalar(BlsScalar::from(i as u64)))
-                .unwrap();
         }
 
-        // Our branch will be on 567
-        let branch = ptree.poseidon_branch(index).unwrap().unwrap();
+        let merkle_opening = db.opening(&note).unwrap();
+        let input = note.to_transaction_input(merkle_opening, sk).unwrap();
 
         let mut composer = StandardComposer::new();
-        merkle(&mut composer, branch, &input);
+        let note_hash = composer.add_input(input.note().hash());
+        let root = zk::public_input(&mut composer, db.root().unwrap());
+        merkle(&mut composer, &input, note_hash, root);
         composer.add_dummy_constraints();
 
         // Generate Composer & Public Parameters
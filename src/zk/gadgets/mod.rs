@@ -1,8 +1,12 @@
+use crate::BlsScalar;
+
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
+
 pub use balance::balance;
-pub use commitment::commitment;
+pub use commitment::{commitment, ValueCommitment};
 pub use merkle::merkle;
 pub use nullifier::nullifier;
-pub use preimage::input_preimage;
+pub use preimage::{input_preimage, NotePreimage};
 pub use range::range;
 
 mod balance;
@@ -11,3 +15,17 @@ mod merkle;
 mod nullifier;
 mod preimage;
 mod range;
+
+/// Constrain two variables to hold the same value
+pub fn constrain_equal(composer: &mut StandardComposer, a: Variable, b: Variable) {
+    composer.add_gate(
+        a,
+        b,
+        composer.zero_var,
+        BlsScalar::one(),
+        -BlsScalar::one(),
+        BlsScalar::zero(),
+        BlsScalar::zero(),
+        BlsScalar::zero(),
+    );
+}
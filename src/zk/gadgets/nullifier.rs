@@ -1,48 +1,64 @@
-use crate::{BlsScalar, Note, TransactionInput, TransactionItem};
+use super::commitment::{bits, bits_sum, fixed_base_mul};
+use super::{constrain_equal, NotePreimage};
+use crate::{BlsScalar, JubJubExtended, Note, TransactionInput, TransactionItem};
 
-use dusk_plonk::constraint_system::StandardComposer;
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
+use jubjub::GENERATOR;
 use poseidon252::sponge::sponge::sponge_hash_gadget;
 
-/// Prove knowledge of the input nullifier
-pub fn nullifier(composer: &mut StandardComposer, input: &TransactionInput) {
+/// Prove knowledge of the input nullifier `H(sk_r, idx)`
+///
+/// The position is the one of the note pre-image, and `sk_r` is bound to the note with
+/// `sk_r · G == pk_r`, so the nullifier can't be computed for a note other than the spent one.
+///
+/// The nullifier is expected to be a public input of the circuit
+pub fn nullifier(
+    composer: &mut StandardComposer,
+    input: &TransactionInput,
+    preimage: &NotePreimage,
+    nullifier: Variable,
+) {
     let sk_r = input.note().sk_r(input.sk());
-    let sk_r = composer.add_input(BlsScalar::from_bytes(&sk_r.to_bytes()).unwrap());
-    let idx = composer.add_input(BlsScalar::from(input.note().idx()));
+    let sk_r_bits = bits(composer, &sk_r.to_bytes());
 
-    let output = sponge_hash_gadget(composer, &[sk_r, idx]);
+    let pk_r = fixed_base_mul(composer, JubJubExtended::from(GENERATOR), &sk_r_bits);
+    constrain_equal(composer, pk_r.x, preimage.pk_r_x);
+    constrain_equal(composer, pk_r.y, preimage.pk_r_y);
+
+    let sk_r = bits_sum(composer, &sk_r_bits);
+    let output = sponge_hash_gadget(composer, &[sk_r, preimage.idx]);
 
     composer.add_gate(
         output,
-        composer.zero_var,
+        nullifier,
         composer.zero_var,
         -BlsScalar::one(),
         BlsScalar::one(),
         BlsScalar::one(),
         BlsScalar::zero(),
-        BlsScalar::from_bytes(&input.nullifier().to_bytes()).unwrap(),
+        BlsScalar::zero(),
     );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{crypto, Note, NoteGenerator, SecretKey, Transaction, TransparentNote};
+    use crate::zk::gadgets;
+    use crate::{crypto, zk, Note, NoteGenerator, SecretKey, TransparentNote};
     use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
     use dusk_plonk::fft::EvaluationDomain;
     use merlin::Transcript;
 
-    #[test]
-    fn nullifier_gadget() {
-        let sk = SecretKey::default();
-        let pk = sk.public_key();
-        let value = 100;
-        let note = TransparentNote::output(&pk, value).0;
-        let merkle_opening = crypto::MerkleProof::mock(note.hash());
-        let input = note.to_transaction_input(merkle_opening, sk).unwrap();
-
+    /// Prove the nullifier of `spent` against the pre-image of `input`, and return the
+    /// verification result
+    fn prove_nullifier(input: &TransactionInput, spent: &TransactionInput) -> bool {
         let mut composer = StandardComposer::new();
 
-        nullifier(&mut composer, &input);
+        let value_commitment = gadgets::commitment(&mut composer, input);
+        let preimage = gadgets::input_preimage(&mut composer, input, &value_commitment);
+
+        let n = zk::public_input(&mut composer, *spent.nullifier().s());
+        nullifier(&mut composer, spent, &preimage, n);
 
         composer.add_dummy_constraints();
 
@@ -59,7 +75,19 @@ mod tests {
 
         let proof = composer.prove(&ck, &circuit, &mut transcript.clone());
 
-        assert!(proof.verify(&circuit, &mut transcript, &vk, &composer.public_inputs()));
+        proof.verify(&circuit, &mut transcript, &vk, &composer.public_inputs())
+    }
+
+    #[test]
+    fn nullifier_gadget() {
+        let sk = SecretKey::default();
+        let pk = sk.public_key();
+        let value = 100;
+        let note = TransparentNote::output(&pk, value).0;
+        let merkle_opening = crypto::MerkleProof::mock(note.hash());
+        let input = note.to_transaction_input(merkle_opening, sk).unwrap();
+
+        assert!(prove_nullifier(&input, &input));
     }
 
     #[test]
@@ -74,25 +102,34 @@ mod tests {
         // Mess up the nullifier
         txi.nullifier = note.generate_nullifier(&SecretKey::default());
 
-        let mut composer = StandardComposer::new();
+        assert!(!prove_nullifier(&txi, &txi));
+    }
 
-        nullifier(&mut composer, &txi);
+    #[test]
+    fn nullifier_unbound_to_note() {
+        let sk = SecretKey::default();
+        let pk = sk.public_key();
+        let value = 61;
+        let note = TransparentNote::output(&pk, value).0;
+        let merkle_opening = crypto::MerkleProof::mock(note.hash());
+        let input = note.to_transaction_input(merkle_opening, sk).unwrap();
 
-        composer.add_dummy_constraints();
+        // Nullifier of the same note at another position
+        let mut moved = note;
+        moved.set_idx(note.idx() + 1);
+        let merkle_opening = crypto::MerkleProof::mock(moved.hash());
+        let moved = moved.to_transaction_input(merkle_opening, sk).unwrap();
+        assert_ne!(moved.nullifier(), input.nullifier());
 
-        // Generate Composer & Public Parameters
-        let pub_params = PublicParameters::setup(1 << 17, &mut rand::thread_rng()).unwrap();
-        let (ck, vk) = pub_params.trim(1 << 16).unwrap();
-        let mut transcript = Transcript::new(b"TEST");
+        assert!(!prove_nullifier(&input, &moved));
 
-        let circuit = composer.preprocess(
-            &ck,
-            &mut transcript,
-            &EvaluationDomain::new(composer.circuit_size()).unwrap(),
-        );
-
-        let proof = composer.prove(&ck, &circuit, &mut transcript.clone());
+        // Nullifier of the same note with a secret key that doesn't own it
+        let merkle_opening = crypto::MerkleProof::mock(note.hash());
+        let forged = note
+            .to_transaction_input(merkle_opening, SecretKey::default())
+            .unwrap();
+        assert_ne!(forged.nullifier(), input.nullifier());
 
-        assert!(!proof.verify(&circuit, &mut transcript, &vk, &composer.public_inputs()));
+        assert!(!prove_nullifier(&input, &forged));
     }
 }
@@ -1,12 +1,34 @@
+use super::ValueCommitment;
 use crate::{BlsScalar, Note, TransactionInput, TransactionItem};
 
-use dusk_plonk::constraint_system::StandardComposer;
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
 use poseidon252::sponge::sponge::sponge_hash_gadget;
 
-/// Prove knowledge of the pre-image of an input note
-pub fn input_preimage(composer: &mut StandardComposer, input: &TransactionInput) {
-    let value_commitment_x = composer.add_input(input.note().value_commitment().get_x());
-    let value_commitment_y = composer.add_input(input.note().value_commitment().get_y());
+/// Variables of the pre-image of an input note allocated on the circuit
+#[derive(Debug, Clone, Copy)]
+pub struct NotePreimage {
+    /// Hash of the note
+    pub hash: Variable,
+    /// Position of the note on the tree
+    pub idx: Variable,
+    /// Affine `x` of the one-time public key of the note
+    pub pk_r_x: Variable,
+    /// Affine `y` of the one-time public key of the note
+    pub pk_r_y: Variable,
+}
+
+/// Prove knowledge of the pre-image of an input note, and return its variables
+///
+/// The value commitment of the pre-image is the one allocated by the [`super::commitment`]
+/// gadget, so the committed value is bound to the spent note. The position and the one-time
+/// public key are returned so the [`super::nullifier`] gadget can bind the nullifier to the note.
+pub fn input_preimage(
+    composer: &mut StandardComposer,
+    input: &TransactionInput,
+    value_commitment: &ValueCommitment,
+) -> NotePreimage {
+    let value_commitment_x = value_commitment.x;
+    let value_commitment_y = value_commitment.y;
     let idx = composer.add_input(BlsScalar::from(input.note().idx()));
     let pk_r_affine_x = composer.add_input(input.note().pk_r().get_x());
     let pk_r_affine_y = composer.add_input(input.note().pk_r().get_y());
@@ -32,11 +54,19 @@ pub fn input_preimage(composer: &mut StandardComposer, input: &TransactionInput)
         BlsScalar::zero(),
         BlsScalar::zero(),
     );
+
+    NotePreimage {
+        hash: note_hash,
+        idx,
+        pk_r_x: pk_r_affine_x,
+        pk_r_y: pk_r_affine_y,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::zk::gadgets;
     use crate::{crypto, Note, NoteGenerator, SecretKey, TransparentNote};
     use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
     use dusk_plonk::fft::EvaluationDomain;
@@ -53,7 +83,8 @@ mod tests {
 
         let mut composer = StandardComposer::new();
 
-        input_preimage(&mut composer, &input);
+        let value_commitment = gadgets::commitment(&mut composer, &input);
+        input_preimage(&mut composer, &input, &value_commitment);
         composer.add_dummy_constraints();
 
         // Generate Composer & Public Parameters
@@ -1,11 +1,10 @@
-use crate::{BlsScalar, TransactionItem};
+use dusk_plonk::constraint_system::{StandardComposer, Variable};
 
-use dusk_plonk::constraint_system::StandardComposer;
-
-/// This gadget simply wraps around the composer's `range_gate` function,
-/// but takes in any type that implements the [`TransactionItem`] trait,
-/// for ease-of-use in circuit construction.
-pub fn range<T: TransactionItem>(composer: &mut StandardComposer, item: &T) {
-    let value = composer.add_input(BlsScalar::from(item.value()));
+/// This gadget simply wraps around the composer's `range_gate` function, constraining the
+/// provided value to 64 bits.
+///
+/// The value is expected to be the one allocated by the [`super::commitment`] gadget, so the
+/// range proof applies to the committed value.
+pub fn range(composer: &mut StandardComposer, value: Variable) {
     composer.range_gate(value, 64);
 }
@@ -0,0 +1,403 @@
+use crate::{zk, BlsScalar, Error, Transaction, TransactionInput, TransactionOutput};
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bincode::{deserialize, serialize};
+use dusk_plonk::commitment_scheme::kzg10::{ProverKey, PublicParameters, VerifierKey};
+use dusk_plonk::constraint_system::StandardComposer;
+use dusk_plonk::fft::EvaluationDomain;
+use dusk_plonk::proof_system::{PreProcessedCircuit, Proof};
+use merlin::Transcript;
+use rayon::prelude::*;
+use tracing::trace;
+
+/// Version of the serialized keys format. Must be bumped every time the transaction circuit
+/// description changes
+pub const KEYS_VERSION: u8 = 2;
+
+/// Environment variable with the location of the keys file
+pub const KEYS_PATH_ENV: &str = "PHOENIX_ZK_KEYS";
+
+/// Environment variable with the location of the structured reference string the keys are
+/// derived from
+pub const SRS_PATH_ENV: &str = "PHOENIX_ZK_SRS";
+
+const KEYS_MAGIC: [u8; 4] = *b"PHXK";
+
+/// Preprocessed transaction circuit for a given number of inputs and outputs
+struct ShapedCircuit {
+    shape: (usize, usize),
+    circuit: PreProcessedCircuit,
+    transcript: Transcript,
+    size: usize,
+    pi_positions: Vec<usize>,
+}
+
+impl ShapedCircuit {
+    fn new(shape: (usize, usize), circuit: PreProcessedCircuit) -> Result<Self, Error> {
+        let (composer, pi_positions) = Keys::describe_circuit(shape);
+        let size = composer.circuit_size();
+
+        let circuit_bytes = serialize(&circuit)?;
+        let transcript = Keys::seed_transcript(circuit_bytes.as_slice());
+
+        Ok(Self {
+            shape,
+            circuit,
+            transcript,
+            size,
+            pi_positions,
+        })
+    }
+}
+
+/// Trimmed public parameters and preprocessed transaction circuits, one for every shape of
+/// [`zk::CIRCUITS`].
+///
+/// Provers and verifiers must derive the keys from the same structured reference string in order
+/// to agree on the proofs. The keys can be persisted via [`Keys::persist`] and restored via
+/// [`Keys::load`].
+pub struct Keys {
+    ck: ProverKey,
+    vk: VerifierKey,
+    circuits: Vec<ShapedCircuit>,
+}
+
+impl Keys {
+    /// Trim the provided structured reference string, and preprocess the transaction circuits.
+    ///
+    /// The preprocessing is deterministic, so every node setting up its keys from the same
+    /// reference string agrees on the proofs
+    pub fn setup(pub_params: &PublicParameters) -> Result<Self, Error> {
+        let (ck, vk) = pub_params.trim(zk::CAPACITY).map_err(Error::generic)?;
+
+        // The circuit description doesn't depend on the witness, so default items are used
+        let circuits = zk::CIRCUITS
+            .iter()
+            .map(|shape| {
+                let (mut composer, _) = Self::describe_circuit(*shape);
+                let size = composer.circuit_size();
+
+                let circuit = composer.preprocess(
+                    &ck,
+                    &mut Transcript::new(b"dusk-phoenix"),
+                    &EvaluationDomain::new(size).unwrap(),
+                );
+
+                ShapedCircuit::new(*shape, circuit)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { ck, vk, circuits })
+    }
+
+    /// Load a set of keys previously stored with [`Keys::persist`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let bytes = fs::read(path)?;
+
+        Self::from_bytes(bytes.as_slice())
+    }
+
+    /// Load the keys from the provided path. If the file doesn't exist, set up the keys from the
+    /// structured reference string stored on `srs` and persist them.
+    ///
+    /// Any other failure, such as a corrupt or outdated keys file, is reported instead of
+    /// replacing the keys.
+    pub fn load_or_setup<P: AsRef<Path>, S: AsRef<Path>>(path: P, srs: S) -> Result<Self, Error> {
+        match Self::load(&path) {
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                trace!(
+                    "Setting up zk keys on {} from {}",
+                    path.as_ref().display(),
+                    srs.as_ref().display()
+                );
+
+                let keys = Self::setup(&load_srs(srs)?)?;
+                keys.persist(&path)?;
+
+                Ok(keys)
+            }
+            result => result,
+        }
+    }
+
+    /// Store the keys on the provided path.
+    ///
+    /// The keys are written to a new, uniquely named file that is synced and then renamed over
+    /// the path, so concurrent readers never observe partial keys.
+    pub fn persist<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .ok_or(Error::InvalidEncoding("Keys.path"))?
+            .to_string_lossy();
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.{:016x}.tmp",
+            name,
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+
+        let mut file = OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+        let result = file
+            .write_all(self.to_bytes()?.as_slice())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp, path));
+
+        if result.is_err() {
+            fs::remove_file(&tmp).ok();
+        }
+
+        Ok(result?)
+    }
+
+    /// Serialize the keys in the format `magic | version | bincode(keys)`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let circuits: Vec<(u64, u64, &PreProcessedCircuit)> = self
+            .circuits
+            .iter()
+            .map(|c| (c.shape.0 as u64, c.shape.1 as u64, &c.circuit))
+            .collect();
+
+        let keys = serialize(&(&self.ck, &self.vk, circuits))?;
+
+        let mut bytes = Vec::with_capacity(KEYS_MAGIC.len() + 1 + keys.len());
+        bytes.extend_from_slice(&KEYS_MAGIC);
+        bytes.push(KEYS_VERSION);
+        bytes.extend_from_slice(keys.as_slice());
+
+        Ok(bytes)
+    }
+
+    /// Deserialize a set of keys created with [`Keys::to_bytes`].
+    ///
+    /// Will fail if the keys were created for a different set of circuits.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let header = KEYS_MAGIC.len() + 1;
+        if bytes.len() < header
+            || bytes[..KEYS_MAGIC.len()] != KEYS_MAGIC
+            || bytes[KEYS_MAGIC.len()] != KEYS_VERSION
+        {
+            return Err(Error::InvalidEncoding("Keys"));
+        }
+
+        let (ck, vk, circuits): (ProverKey, VerifierKey, Vec<(u64, u64, PreProcessedCircuit)>) =
+            deserialize(&bytes[header..])?;
+
+        if circuits.len() != zk::CIRCUITS.len() {
+            return Err(Error::InvalidLength {
+                field: "Keys.circuits",
+                expected: zk::CIRCUITS.len(),
+                actual: circuits.len(),
+            });
+        }
+
+        let circuits = circuits
+            .into_iter()
+            .zip(zk::CIRCUITS.iter())
+            .map(|((inputs, outputs, circuit), shape)| {
+                if (inputs as usize, outputs as usize) != *shape {
+                    return Err(Error::InvalidEncoding("Keys.circuits"));
+                }
+
+                ShapedCircuit::new(*shape, circuit)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { ck, vk, circuits })
+    }
+
+    /// Generate a new transaction zk proof with the circuit that matches the transaction shape
+    pub fn prove(&self, tx: &Transaction) -> Result<Proof, Error> {
+        let shape = tx.circuit_shape().ok_or(Error::InvalidShape)?;
+        let circuit = self
+            .circuits
+            .iter()
+            .find(|c| c.shape == shape)
+            .ok_or(Error::InvalidShape)?;
+
+        let mut composer = StandardComposer::with_expected_size(zk::CAPACITY);
+        zk::circuit(&mut composer, tx);
+
+        if composer.circuit_size() != circuit.size {
+            return Err(Error::InvalidLength {
+                field: "Keys.circuits",
+                expected: circuit.size,
+                actual: composer.circuit_size(),
+            });
+        }
+
+        Ok(composer.prove(&self.ck, &circuit.circuit, &mut circuit.transcript.clone()))
+    }
+
+    /// Verify a proof against the preprocessed circuit selected by the shape public inputs
+    ///
+    /// The public inputs are expected in the order of [`zk::ZkPublicInputs::generate_pi`]
+    pub fn verify(&self, proof: &Proof, pi: &[BlsScalar]) -> bool {
+        let circuit = match pi {
+            [inputs, outputs, ..] => self.circuits.iter().find(|c| {
+                *inputs == BlsScalar::from(c.shape.0 as u64)
+                    && *outputs == BlsScalar::from(c.shape.1 as u64)
+            }),
+            _ => None,
+        };
+
+        let circuit = match circuit {
+            Some(c) if c.pi_positions.len() == pi.len() => c,
+            _ => return false,
+        };
+
+        let mut public_inputs = vec![BlsScalar::zero(); circuit.size];
+        circuit
+            .pi_positions
+            .iter()
+            .zip(pi.iter())
+            .for_each(|(p, s)| public_inputs[*p] = *s);
+
+        proof.verify(
+            &circuit.circuit,
+            &mut circuit.transcript.clone(),
+            &self.vk,
+            &public_inputs,
+        )
+    }
+
+    /// Verify a batch of proofs, each against its own public inputs.
+    ///
+    /// Every proof is verified on its own, across the rayon thread pool; see [`zk::verify_batch`]
+    /// for the reasons the pairing checks are not aggregated.
+    ///
+    /// Fail with [`Error::InvalidBatch`] reporting the positions of all the invalid proofs
+    pub fn verify_batch(&self, proofs: &[(&Proof, &[BlsScalar])]) -> Result<(), Error> {
+        let invalid: Vec<usize> = proofs
+            .par_iter()
+            .enumerate()
+            .filter(|(_, (proof, pi))| !self.verify(proof, pi))
+            .map(|(i, _)| i)
+            .collect();
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidBatch(invalid))
+        }
+    }
+
+    fn describe_circuit(shape: (usize, usize)) -> (StandardComposer, Vec<usize>) {
+        let mut tx = Transaction::default();
+        (0..shape.0).for_each(|_| tx.push_input(TransactionInput::default()).unwrap());
+        (0..shape.1).for_each(|_| tx.push_output(TransactionOutput::default()).unwrap());
+
+        let mut composer = StandardComposer::with_expected_size(zk::CAPACITY);
+        let pi_positions = zk::circuit(&mut composer, &tx);
+
+        (composer, pi_positions)
+    }
+
+    /// Bind the proofs transcript to the preprocessed circuit, so it can be reconstructed after
+    /// the keys are restored
+    fn seed_transcript(circuit: &[u8]) -> Transcript {
+        let mut transcript = Transcript::new(b"dusk-phoenix");
+        transcript.append_message(b"circuit", circuit);
+
+        transcript
+    }
+}
+
+/// Load a structured reference string, stored as the bincode serialization of the
+/// [`PublicParameters`] of the trusted setup
+pub fn load_srs<P: AsRef<Path>>(path: P) -> Result<PublicParameters, Error> {
+    let bytes = fs::read(path)?;
+
+    Ok(deserialize(bytes.as_slice())?)
+}
+
+/// Location of the file configured by the provided environment variable.
+///
+/// There is no default, so the keys are never read from a shared location by accident
+pub fn env_path(var: &'static str) -> Result<PathBuf, Error> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .ok_or(Error::MissingConfiguration(var))
+}
+
+/// Load the keys from [`KEYS_PATH_ENV`], setting them up from the structured reference string
+/// on [`SRS_PATH_ENV`] if the keys file doesn't exist
+pub fn from_env() -> Result<Keys, Error> {
+    Keys::load_or_setup(env_path(KEYS_PATH_ENV)?, env_path(SRS_PATH_ENV)?)
+}
+
+/// Structured reference string of an insecure trusted setup, for the tests only
+#[cfg(test)]
+pub(crate) fn test_srs() -> PublicParameters {
+    PublicParameters::setup(zk::CAPACITY << 1, &mut rand::thread_rng()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::Rng;
+    use tempdir::TempDir;
+
+    #[test]
+    fn keys_persistence() {
+        let dir = TempDir::new("keys_persistence").unwrap();
+        let path = dir.path().join("keys.bin");
+        let srs = dir.path().join("srs.bin");
+        fs::write(&srs, serialize(&test_srs()).unwrap()).unwrap();
+
+        let keys = Keys::load_or_setup(&path, &srs).unwrap();
+        let restored = Keys::load(&path).unwrap();
+        assert_eq!(keys.to_bytes().unwrap(), restored.to_bytes().unwrap());
+
+        // The keys set up from the same reference string are the same
+        let other = Keys::setup(&load_srs(&srs).unwrap()).unwrap();
+        assert_eq!(keys.to_bytes().unwrap(), other.to_bytes().unwrap());
+
+        let mut tx: Transaction = rand::thread_rng().gen();
+        tx.sort_items();
+        let pi = zk::ZkPublicInputs::from(&tx).generate_pi();
+
+        let proof = keys.prove(&tx).unwrap();
+        assert!(restored.verify(&proof, pi.as_slice()));
+
+        let proof = restored.prove(&tx).unwrap();
+        assert!(keys.verify(&proof, pi.as_slice()));
+    }
+
+    #[test]
+    fn keys_load_failure() {
+        let dir = TempDir::new("keys_load_failure").unwrap();
+        let path = dir.path().join("keys.bin");
+        let srs = dir.path().join("srs.bin");
+
+        // Without a reference string, the keys can't be set up
+        assert!(Keys::load_or_setup(&path, &srs).is_err());
+        assert!(!path.exists());
+
+        // A corrupt keys file is reported, and left untouched
+        fs::write(&srs, serialize(&test_srs()).unwrap()).unwrap();
+        fs::write(&path, b"PHXK").unwrap();
+        assert!(Keys::load_or_setup(&path, &srs).is_err());
+        assert_eq!(b"PHXK".to_vec(), fs::read(&path).unwrap());
+
+        // No temporary file is left behind
+        let keys = Keys::setup(&test_srs()).unwrap();
+        keys.persist(&path).unwrap();
+        assert_eq!(2, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn keys_version() {
+        let keys = Keys::setup(&test_srs()).unwrap();
+
+        let mut bytes = keys.to_bytes().unwrap();
+        bytes[KEYS_MAGIC.len()] = KEYS_VERSION + 1;
+
+        assert!(Keys::from_bytes(bytes.as_slice()).is_err());
+        assert!(Keys::from_bytes(&bytes[..KEYS_MAGIC.len()]).is_err());
+    }
+}
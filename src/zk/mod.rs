@@ -1,47 +1,166 @@
-use crate::{BlsScalar, Transaction};
+use crate::{BlsScalar, Error, Transaction};
+
+use once_cell::sync::OnceCell;
 
-use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
 pub use dusk_plonk::constraint_system::{StandardComposer, Variable};
-use dusk_plonk::fft::EvaluationDomain;
 pub use dusk_plonk::proof_system::{PreProcessedCircuit, Proof};
-use merlin::Transcript;
 
-pub const CAPACITY: usize = 8192 * 8;
+pub const CAPACITY: usize = 8192 * 16;
+
+/// Number of inputs and outputs of every supported transaction circuit
+pub const CIRCUITS: [(usize, usize); 4] = [(1, 2), (2, 2), (3, 2), (4, 2)];
 
 pub const SERIALIZED_PROOF_SIZE: usize = 1097;
 
 /// Circuit gadgets
 pub mod gadgets;
 
+/// Proving and verifying keys management
+pub mod keys;
+pub use keys::Keys;
+
 mod public_inputs;
 pub use public_inputs::ZkPublicInputs;
 
-/// Generate a new transaction zk proof
-pub fn prove(tx: &mut Transaction) -> Proof {
-    let mut composer = StandardComposer::with_expected_size(CAPACITY);
-
-    // TODO: use actual circuit
-    composer.add_dummy_constraints();
-    composer.add_dummy_constraints();
+static KEYS: OnceCell<Keys> = OnceCell::new();
 
-    let pub_params = PublicParameters::setup(1 << 17, &mut rand::thread_rng()).unwrap();
-    let (ck, vk) = pub_params.trim(1 << 16).unwrap();
-    let mut transcript = Transcript::new(b"dusk-phoenix");
+/// Allocate a public input on the circuit, and return its variable
+pub fn public_input(composer: &mut StandardComposer, value: BlsScalar) -> Variable {
+    let var = composer.add_input(value);
 
-    let circuit = composer.preprocess(
-        &ck,
-        &mut transcript,
-        &EvaluationDomain::new(composer.circuit_size()).unwrap(),
+    composer.add_gate(
+        var,
+        composer.zero_var,
+        composer.zero_var,
+        -BlsScalar::one(),
+        BlsScalar::one(),
+        BlsScalar::one(),
+        BlsScalar::zero(),
+        value,
     );
 
-    composer.prove(&ck, &circuit, &mut transcript.clone())
+    var
+}
+
+/// Return the shape of the smallest circuit that can prove a transaction with the provided number
+/// of inputs and outputs.
+///
+/// Every input must be spent, but missing outputs are padded with zero-value dummies
+pub fn circuit_shape(inputs: usize, outputs: usize) -> Option<(usize, usize)> {
+    CIRCUITS
+        .iter()
+        .find(|(i, o)| *i == inputs && outputs <= *o)
+        .copied()
+}
+
+/// Append the transaction circuit to the composer, composing the gadgets for every input, output
+/// and the fee.
+///
+/// Return the positions of the public inputs on the circuit, following the order of
+/// [`ZkPublicInputs::generate_pi`]
+pub fn circuit(composer: &mut StandardComposer, tx: &Transaction) -> Vec<usize> {
+    let pi = ZkPublicInputs::from(tx).generate_pi();
+    let mut positions = Vec::with_capacity(pi.len());
+
+    let pi: Vec<Variable> = pi
+        .into_iter()
+        .map(|p| {
+            positions.push(composer.circuit_size());
+            public_input(composer, p)
+        })
+        .collect();
+
+    // The first public inputs are the circuit shape and the fee value commitment
+    let inputs_pi = &pi[3..3 + 2 * tx.inputs().len()];
+    let outputs_pi = &pi[3 + 2 * tx.inputs().len()..];
+
+    let inputs: Vec<Variable> = tx
+        .inputs()
+        .iter()
+        .zip(inputs_pi.chunks(2))
+        .map(|(input, pi)| {
+            let value_commitment = gadgets::commitment(composer, input);
+            let preimage = gadgets::input_preimage(composer, input, &value_commitment);
+
+            gadgets::merkle(composer, input, preimage.hash, pi[0]);
+            gadgets::nullifier(composer, input, &preimage, pi[1]);
+
+            value_commitment.value
+        })
+        .collect();
+
+    // The dummy outputs padding the transaction up to its circuit shape are included, so the
+    // circuit description doesn't depend on the number of pushed outputs. They carry no value.
+    let outputs: Vec<Variable> = tx
+        .circuit_outputs()
+        .iter()
+        .zip(outputs_pi.chunks(2))
+        .map(|(output, pi)| {
+            let value_commitment = gadgets::commitment(composer, output);
+            gadgets::constrain_equal(composer, value_commitment.x, pi[0]);
+            gadgets::range(composer, value_commitment.value);
+
+            value_commitment.value
+        })
+        .collect();
+
+    let fee = gadgets::commitment(composer, tx.fee());
+    gadgets::constrain_equal(composer, fee.x, pi[2]);
+    gadgets::range(composer, fee.value);
+
+    gadgets::balance(composer, &inputs, &outputs, fee.value);
+
+    positions
+}
+
+/// Load or generate the keys used by [`prove`] and [`verify`].
+///
+/// The keys are stored on [`keys::KEYS_PATH_ENV`], and set up from the structured reference
+/// string on [`keys::SRS_PATH_ENV`] if missing. They will be lazily initialized on the first proof
+/// otherwise.
+///
+/// Fail if the variables are not set, or the keys can't be loaded. Nothing is cached on failure,
+/// so the keys are loaded again on the next call.
+pub fn init() -> Result<(), Error> {
+    keys().map(|_| ())
+}
+
+/// Return the keys used by [`prove`] and [`verify`], loading them on the first call
+pub fn keys() -> Result<&'static Keys, Error> {
+    KEYS.get_or_try_init(load_keys)
+}
+
+#[cfg(not(test))]
+fn load_keys() -> Result<Keys, Error> {
+    keys::from_env()
+}
+
+#[cfg(test)]
+fn load_keys() -> Result<Keys, Error> {
+    Keys::setup(&keys::test_srs())
+}
+
+/// Generate a new transaction zk proof
+pub fn prove(tx: &Transaction) -> Result<Proof, Error> {
+    keys()?.prove(tx)
 }
 
 /// Verify a proof with a pre-generated circuit
-pub fn verify(proof: &Proof, pi: &[BlsScalar]) -> bool {
-    // let mut transcript = TRANSCRIPT.clone();
-    // let preprocessed_circuit = circuit();
+///
+/// The public inputs are expected in the order of [`ZkPublicInputs::generate_pi`]. Fail only if
+/// the keys can't be loaded.
+pub fn verify(proof: &Proof, pi: &[BlsScalar]) -> Result<bool, Error> {
+    Ok(keys()?.verify(proof, pi))
+}
 
-    // proof.verify(preprocessed_circuit, &mut transcript, &*VK, &pi.to_vec())
-    true
+/// Verify a batch of proofs, each against its own public inputs
+///
+/// This is not an aggregated check: every proof runs its own pairing checks, in parallel, so the
+/// total cost is the one of as many single verifications. Combining the pairings of the batch
+/// with a random linear combination requires the opening checks of the plonk verifier, which it
+/// doesn't expose.
+///
+/// Fail with [`Error::InvalidBatch`] reporting the positions of all the invalid proofs
+pub fn verify_batch(proofs: &[(&Proof, &[BlsScalar])]) -> Result<(), Error> {
+    keys()?.verify_batch(proofs)
 }
@@ -1,5 +1,5 @@
 use crate::{
-    utils, zk, BlsScalar, Error, JubJubAffine, Note, Nullifier, Transaction, TransactionItem,
+    utils, BlsScalar, Error, JubJubAffine, Note, Nullifier, Transaction, TransactionItem,
     MAX_INPUT_NOTES_PER_TRANSACTION, MAX_OUTPUT_NOTES_PER_TRANSACTION,
 };
 
@@ -7,13 +7,60 @@ use std::io::{self, Read, Write};
 
 use unprolix::{Constructor, Getters, Setters};
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Constructor, Getters, Setters)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Constructor, Getters, Setters)]
 pub struct ZkPublicInputs {
     fee_value_commitment: BlsScalar,
-    merkle_roots: [BlsScalar; MAX_INPUT_NOTES_PER_TRANSACTION],
-    nullifiers: [Nullifier; MAX_INPUT_NOTES_PER_TRANSACTION],
-    outputs_value_commitments: [BlsScalar; MAX_OUTPUT_NOTES_PER_TRANSACTION],
-    outputs_pk_r_affine_x: [BlsScalar; MAX_OUTPUT_NOTES_PER_TRANSACTION],
+    merkle_roots: Vec<BlsScalar>,
+    nullifiers: Vec<Nullifier>,
+    outputs_value_commitments: Vec<BlsScalar>,
+    outputs_pk_r_affine_x: Vec<BlsScalar>,
+}
+
+impl ZkPublicInputs {
+    /// Return the public inputs in the order they are allocated on the transaction circuit.
+    ///
+    /// The first two public inputs are the number of inputs and outputs of the circuit, so the
+    /// verifier can select the matching preprocessed circuit
+    pub fn generate_pi(&self) -> Vec<BlsScalar> {
+        let mut pi = vec![
+            BlsScalar::from(self.merkle_roots.len() as u64),
+            BlsScalar::from(self.outputs_value_commitments.len() as u64),
+            self.fee_value_commitment,
+        ];
+
+        self.merkle_roots
+            .iter()
+            .zip(self.nullifiers.iter())
+            .for_each(|(r, n)| {
+                pi.push(*r);
+                pi.push(*n.s());
+            });
+
+        self.outputs_value_commitments
+            .iter()
+            .zip(self.outputs_pk_r_affine_x.iter())
+            .for_each(|(c, x)| {
+                pi.push(*c);
+                pi.push(*x);
+            });
+
+        pi
+    }
+}
+
+/// Decode a number of items serialized as a scalar, bounded by `max`
+fn scalar_to_len(s: BlsScalar, max: usize) -> Result<usize, Error> {
+    let bytes = s.to_bytes();
+
+    let mut len = [0x00u8; 8];
+    len.copy_from_slice(&bytes[0..8]);
+    let len = u64::from_le_bytes(len) as usize;
+
+    if bytes[8..].iter().any(|b| *b != 0) || len > max {
+        return Err(Error::InvalidParameters);
+    }
+
+    Ok(len)
 }
 
 impl Write for ZkPublicInputs {
@@ -21,44 +68,35 @@ impl Write for ZkPublicInputs {
         let mut chunk = buf.chunks(utils::BLS_SCALAR_SERIALIZED_SIZE);
         let mut n = 0;
 
-        self.fee_value_commitment = chunk
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(utils::deserialize_bls_scalar)
-            .map_err::<io::Error, _>(|e| e.into())?;
-        n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-
-        for i in 0..MAX_INPUT_NOTES_PER_TRANSACTION {
-            self.merkle_roots[i] = chunk
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(utils::deserialize_bls_scalar)
-                .map_err::<io::Error, _>(|e| e.into())?;
+        let mut next = || {
             n += utils::BLS_SCALAR_SERIALIZED_SIZE;
 
-            self.nullifiers[i] = chunk
+            chunk
                 .next()
                 .ok_or(Error::InvalidParameters)
                 .and_then(utils::deserialize_bls_scalar)
-                .map_err::<io::Error, _>(|e| e.into())?
-                .into();
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-        }
+                .map_err::<io::Error, _>(|e| e.into())
+        };
 
-        for i in 0..MAX_OUTPUT_NOTES_PER_TRANSACTION {
-            self.outputs_value_commitments[i] = chunk
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(utils::deserialize_bls_scalar)
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
+        let inputs = scalar_to_len(next()?, MAX_INPUT_NOTES_PER_TRANSACTION)
+            .map_err::<io::Error, _>(|e| e.into())?;
+        let outputs = scalar_to_len(next()?, MAX_OUTPUT_NOTES_PER_TRANSACTION)
+            .map_err::<io::Error, _>(|e| e.into())?;
 
-            self.outputs_pk_r_affine_x[i] = chunk
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(utils::deserialize_bls_scalar)
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
+        self.fee_value_commitment = next()?;
+
+        self.merkle_roots.clear();
+        self.nullifiers.clear();
+        for _ in 0..inputs {
+            self.merkle_roots.push(next()?);
+            self.nullifiers.push(next()?.into());
+        }
+
+        self.outputs_value_commitments.clear();
+        self.outputs_pk_r_affine_x.clear();
+        for _ in 0..outputs {
+            self.outputs_value_commitments.push(next()?);
+            self.outputs_pk_r_affine_x.push(next()?);
         }
 
         Ok(n)
@@ -74,43 +112,12 @@ impl Read for ZkPublicInputs {
         let mut chunk = buf.chunks_mut(utils::BLS_SCALAR_SERIALIZED_SIZE);
         let mut n = 0;
 
-        chunk
-            .next()
-            .ok_or(Error::InvalidParameters)
-            .and_then(|c| Ok(c.copy_from_slice(&self.fee_value_commitment.to_bytes()[..])))
-            .map_err::<io::Error, _>(|e| e.into())?;
-        n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-
-        for i in 0..MAX_INPUT_NOTES_PER_TRANSACTION {
-            chunk
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(|c| Ok(c.copy_from_slice(&self.merkle_roots[i].to_bytes()[..])))
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-
-            chunk
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(|c| Ok(c.copy_from_slice(&self.nullifiers[i].s().to_bytes()[..])))
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-        }
-
-        for i in 0..MAX_OUTPUT_NOTES_PER_TRANSACTION {
-            chunk
-                .next()
-                .ok_or(Error::InvalidParameters)
-                .and_then(|c| {
-                    Ok(c.copy_from_slice(&self.outputs_value_commitments[i].to_bytes()[..]))
-                })
-                .map_err::<io::Error, _>(|e| e.into())?;
-            n += utils::BLS_SCALAR_SERIALIZED_SIZE;
-
+        for pi in self.generate_pi() {
             chunk
                 .next()
+                .filter(|c| c.len() == utils::BLS_SCALAR_SERIALIZED_SIZE)
                 .ok_or(Error::InvalidParameters)
-                .and_then(|c| Ok(c.copy_from_slice(&self.outputs_pk_r_affine_x[i].to_bytes()[..])))
+                .and_then(|c| Ok(c.copy_from_slice(&pi.to_bytes()[..])))
                 .map_err::<io::Error, _>(|e| e.into())?;
             n += utils::BLS_SCALAR_SERIALIZED_SIZE;
         }
@@ -127,31 +134,23 @@ impl From<&Transaction> for ZkPublicInputs {
     fn from(tx: &Transaction) -> Self {
         let fee_value_commitment = tx.fee().note().value_commitment().get_x();
 
-        let mut merkle_roots = [BlsScalar::zero(); MAX_INPUT_NOTES_PER_TRANSACTION];
-        let mut nullifiers = [Nullifier::default(); MAX_INPUT_NOTES_PER_TRANSACTION];
-
-        tx.inputs()
+        let (merkle_roots, nullifiers) = tx
+            .inputs()
             .iter()
-            .zip(merkle_roots.iter_mut().zip(nullifiers.iter_mut()))
-            .for_each(|(i, (r, n))| {
-                *r = i.merkle_root;
-                *n = *i.nullifier();
-            });
+            .map(|i| (i.merkle_root, *i.nullifier()))
+            .unzip();
 
-        let mut outputs_value_commitments = [BlsScalar::zero(); MAX_OUTPUT_NOTES_PER_TRANSACTION];
-        let mut outputs_pk_r_affine_x = [BlsScalar::zero(); MAX_OUTPUT_NOTES_PER_TRANSACTION];
-
-        tx.outputs()
+        // Dummy outputs are allocated on the circuit as well, so they are part of the public inputs
+        let (outputs_value_commitments, outputs_pk_r_affine_x) = tx
+            .circuit_outputs()
             .iter()
-            .zip(
-                outputs_value_commitments
-                    .iter_mut()
-                    .zip(outputs_pk_r_affine_x.iter_mut()),
-            )
-            .for_each(|(o, (c, x))| {
-                *c = o.note().value_commitment().get_x();
-                *x = JubJubAffine::from(o.note().pk_r()).get_x();
-            });
+            .map(|o| {
+                (
+                    o.note().value_commitment().get_x(),
+                    JubJubAffine::from(o.note().pk_r()).get_x(),
+                )
+            })
+            .unzip();
 
         ZkPublicInputs::new(
             fee_value_commitment,